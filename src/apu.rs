@@ -1,89 +1,884 @@
-use js_sys::SharedArrayBuffer;
+use wasm_bindgen::prelude::*;
 
-const SAMPLE_SIZE: usize = 44100 / 60;
-const SAMPLE_RATE: usize = 44100;
+use crate::error::EmuError;
+use crate::mixer::{Mixer, DEFAULT_OUTPUT_RATE};
+use crate::recorder::{Recorder, Recording};
+use crate::state::{Savable, StateReader, StateWriter};
+
+// each channel's digital output is kept for the last frame at 44.1 kHz
+const SCOPE_SIZE: usize = 44100 / 60;
+const SCOPE_RATE: usize = 44100;
+const M_CYCLES_PER_SECOND: usize = 1_048_576;
+const T_CYCLES_PER_SECOND: f32 = 4_194_304.0;
+// channel timers count T-cycles, the APU is ticked once per M-cycle
+const T_CYCLES: i32 = 4;
+
+// the frame sequencer steps on the falling edge of DIV bit 4 (bit 12 of the internal counter)
+const FRAME_SEQUENCER_BIT: u16 = 12;
+
+const DUTY_TABLE: [u8; 4] = [
+    0b0000_0001,
+    0b1000_0001,
+    0b1000_0111,
+    0b0111_1110,
+];
+
+const NOISE_DIVISORS: [i32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// bits that always read back as 1, from NR10 (0xFF10) to NR52 (0xFF26)
+const READ_MASKS: [u8; 0x17] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF,
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+    0xFF, 0xFF, 0x00, 0x00, 0xBF,
+    0x00, 0x00, 0x70,
+];
+
+struct Envelope {
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    fn trigger(&mut self, nrx2: u8) {
+        self.volume = nrx2 >> 4;
+        self.timer = nrx2 & 7;
+    }
+
+    fn clock(&mut self, nrx2: u8) {
+        let period = nrx2 & 7;
+        if period == 0 {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = period;
+            let increase = nrx2 >> 3 & 1 == 1;
+            if increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+// NRx4 bit 6 gates the length counter, the channel shuts off when it runs out
+fn clock_length(length: &mut u16, nrx4: u8, enabled: &mut bool) {
+    if nrx4 >> 6 & 1 == 1 && *length > 0 {
+        *length -= 1;
+        if *length == 0 {
+            *enabled = false;
+        }
+    }
+}
+
+fn frequency(regs: &[u8; 5]) -> u16 {
+    (regs[4] as u16 & 7) << 8 | regs[3] as u16
+}
+
+// channels 1 and 2, only channel 1 has the sweep unit
+pub struct Square {
+    regs: [u8; 5],
+    has_sweep: bool,
+    enabled: bool,
+    timer: i32,
+    duty_pos: u8,
+    length: u16,
+    envelope: Envelope,
+    sweep_enabled: bool,
+    sweep_timer: u8,
+    shadow: u16,
+}
+
+impl Square {
+    fn new(has_sweep: bool) -> Self {
+        Self {
+            regs: [0; 5],
+            has_sweep,
+            enabled: false,
+            timer: 0,
+            duty_pos: 0,
+            length: 0,
+            envelope: Envelope::new(),
+            sweep_enabled: false,
+            sweep_timer: 0,
+            shadow: 0,
+        }
+    }
+
+    fn dac_enabled(&self) -> bool {
+        self.regs[2] & 0xF8 != 0
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - frequency(&self.regs) as i32) * 4
+    }
+
+    fn write(&mut self, reg: usize, value: u8) {
+        self.regs[reg] = value;
+        match reg {
+            1 => self.load_length(value),
+            2 if !self.dac_enabled() => self.enabled = false,
+            4 if value & 0x80 != 0 => self.trigger(),
+            _ => {}
+        }
+    }
+
+    fn load_length(&mut self, nrx1: u8) {
+        self.length = 64 - (nrx1 & 0x3F) as u16;
+    }
+
+    // everything but the length counter is cleared
+    fn power_off(&mut self) {
+        let length = self.length;
+        *self = Square::new(self.has_sweep);
+        self.length = length;
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        if self.length == 0 {
+            self.length = 64;
+        }
+        self.timer = self.period();
+        self.envelope.trigger(self.regs[2]);
+
+        if self.has_sweep {
+            let period = self.regs[0] >> 4 & 7;
+            let shift = self.regs[0] & 7;
+            self.shadow = frequency(&self.regs);
+            self.sweep_timer = if period == 0 { 8 } else { period };
+            self.sweep_enabled = period != 0 || shift != 0;
+            if shift != 0 {
+                self.sweep_frequency();
+            }
+        }
+    }
+
+    // next sweep frequency, overflowing past 2047 turns the channel off
+    fn sweep_frequency(&mut self) -> u16 {
+        let delta = self.shadow >> (self.regs[0] & 7);
+        let next = if self.regs[0] >> 3 & 1 == 1 {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        if next > 2047 {
+            self.enabled = false;
+        }
+        next
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_timer > 0 {
+            self.sweep_timer -= 1;
+        }
+        if self.sweep_timer != 0 {
+            return;
+        }
+        let period = self.regs[0] >> 4 & 7;
+        self.sweep_timer = if period == 0 { 8 } else { period };
+        if !self.sweep_enabled || period == 0 {
+            return;
+        }
+
+        let next = self.sweep_frequency();
+        if next <= 2047 && self.regs[0] & 7 != 0 {
+            self.shadow = next;
+            self.regs[3] = next as u8;
+            self.regs[4] = (self.regs[4] & !7) | (next >> 8) as u8;
+            self.sweep_frequency();
+        }
+    }
+
+    fn clock_length(&mut self) {
+        clock_length(&mut self.length, self.regs[4], &mut self.enabled);
+    }
+
+    fn clock_envelope(&mut self) {
+        self.envelope.clock(self.regs[2]);
+    }
+
+    fn tick(&mut self) {
+        self.timer -= T_CYCLES;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.duty_pos = (self.duty_pos + 1) % 8;
+        }
+    }
+
+    fn status(&self) -> ChannelStatus {
+        ChannelStatus {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled(),
+            audible: true,
+            // a duty cycle is eight periods long
+            frequency: T_CYCLES_PER_SECOND / (self.period() * 8) as f32,
+            volume: self.envelope.volume,
+            duty: self.regs[1] >> 6,
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let duty = DUTY_TABLE[(self.regs[1] >> 6) as usize];
+        (duty >> (7 - self.duty_pos) & 1) * self.envelope.volume
+    }
+}
+
+// channel 3, plays back the 32 4-bit samples in wave RAM
+pub struct Wave {
+    regs: [u8; 5],
+    ram: [u8; 16],
+    enabled: bool,
+    timer: i32,
+    position: u8,
+    sample: u8,
+    // wave RAM was read during the last tick
+    accessed: bool,
+    length: u16,
+}
+
+impl Wave {
+    fn new() -> Self {
+        Self {
+            regs: [0; 5],
+            ram: [0; 16],
+            enabled: false,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            accessed: false,
+            length: 0,
+        }
+    }
+
+    fn dac_enabled(&self) -> bool {
+        self.regs[0] & 0x80 != 0
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - frequency(&self.regs) as i32) * 2
+    }
+
+    fn write(&mut self, reg: usize, value: u8) {
+        self.regs[reg] = value;
+        match reg {
+            0 if !self.dac_enabled() => self.enabled = false,
+            1 => self.load_length(value),
+            4 if value & 0x80 != 0 => self.trigger(),
+            _ => {}
+        }
+    }
+
+    fn load_length(&mut self, nr31: u8) {
+        self.length = 256 - nr31 as u16;
+    }
+
+    // wave RAM and the length counter survive
+    fn power_off(&mut self) {
+        let (ram, length) = (self.ram, self.length);
+        *self = Wave::new();
+        self.ram = ram;
+        self.length = length;
+    }
+
+    fn trigger(&mut self) {
+        // on the DMG retriggering just as the channel reads a sample corrupts
+        // the start of wave RAM with the block being read
+        if self.enabled && self.timer <= T_CYCLES {
+            let next = ((self.position + 1) % 32 / 2) as usize;
+            if next < 4 {
+                self.ram[0] = self.ram[next];
+            } else {
+                let block = next & !3;
+                self.ram.copy_within(block..block + 4, 0);
+            }
+        }
+
+        self.enabled = self.dac_enabled();
+        if self.length == 0 {
+            self.length = 256;
+        }
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    // while playing, the CPU only gets through on the cycle the channel reads
+    // wave RAM and then sees the byte being played
+    fn read_ram(&self, index: usize) -> u8 {
+        match (self.enabled, self.accessed) {
+            (false, _) => self.ram[index],
+            (true, true) => self.ram[(self.position / 2) as usize],
+            (true, false) => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, index: usize, value: u8) {
+        match (self.enabled, self.accessed) {
+            (false, _) => self.ram[index] = value,
+            (true, true) => self.ram[(self.position / 2) as usize] = value,
+            (true, false) => {}
+        }
+    }
+
+    fn clock_length(&mut self) {
+        clock_length(&mut self.length, self.regs[4], &mut self.enabled);
+    }
+
+    fn tick(&mut self) {
+        self.timer -= T_CYCLES;
+        self.accessed = false;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.accessed = self.enabled;
+            self.position = (self.position + 1) % 32;
+            let byte = self.ram[(self.position / 2) as usize];
+            self.sample = if self.position & 1 == 0 { byte >> 4 } else { byte & 0xF };
+        }
+    }
+
+    // the volume is the loudest sample the output level lets through
+    fn status(&self) -> ChannelStatus {
+        ChannelStatus {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled(),
+            audible: true,
+            frequency: T_CYCLES_PER_SECOND / (self.period() * 32) as f32,
+            volume: match self.regs[2] >> 5 & 3 {
+                0 => 0,
+                code => 15 >> (code - 1),
+            },
+            duty: 0,
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        match self.regs[2] >> 5 & 3 {
+            0 => 0,
+            code => self.sample >> (code - 1),
+        }
+    }
+}
+
+// channel 4, pseudo random noise from a 15 bit LFSR
+pub struct Noise {
+    regs: [u8; 5],
+    enabled: bool,
+    timer: i32,
+    lfsr: u16,
+    length: u16,
+    envelope: Envelope,
+}
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            regs: [0; 5],
+            enabled: false,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: 0,
+            envelope: Envelope::new(),
+        }
+    }
+
+    fn dac_enabled(&self) -> bool {
+        self.regs[2] & 0xF8 != 0
+    }
+
+    fn period(&self) -> i32 {
+        NOISE_DIVISORS[(self.regs[3] & 7) as usize] << (self.regs[3] >> 4)
+    }
+
+    fn write(&mut self, reg: usize, value: u8) {
+        self.regs[reg] = value;
+        match reg {
+            1 => self.load_length(value),
+            2 if !self.dac_enabled() => self.enabled = false,
+            4 if value & 0x80 != 0 => self.trigger(),
+            _ => {}
+        }
+    }
+
+    fn load_length(&mut self, nr41: u8) {
+        self.length = 64 - (nr41 & 0x3F) as u16;
+    }
+
+    fn power_off(&mut self) {
+        let length = self.length;
+        *self = Noise::new();
+        self.length = length;
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        if self.length == 0 {
+            self.length = 64;
+        }
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.envelope.trigger(self.regs[2]);
+    }
+
+    fn clock_length(&mut self) {
+        clock_length(&mut self.length, self.regs[4], &mut self.enabled);
+    }
+
+    fn clock_envelope(&mut self) {
+        self.envelope.clock(self.regs[2]);
+    }
+
+    fn tick(&mut self) {
+        self.timer -= T_CYCLES;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (bit << 14);
+            // 7 bit mode also feeds the result into bit 6
+            if self.regs[3] >> 3 & 1 == 1 {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+            }
+        }
+    }
+
+    // the frequency is how often the LFSR shifts
+    fn status(&self) -> ChannelStatus {
+        ChannelStatus {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled(),
+            audible: true,
+            frequency: T_CYCLES_PER_SECOND / self.period() as f32,
+            volume: self.envelope.volume,
+            duty: 0,
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        (!self.lfsr & 1) as u8 * self.envelope.volume
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelStatus {
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    // false when muted or another channel is soloed
+    pub audible: bool,
+    // Hz of one whole waveform, the noise channel reports its shift rate
+    pub frequency: f32,
+    pub volume: u8,
+    // NRx1 duty index of the square channels
+    pub duty: u8,
+}
 
 pub struct APU {
-    channel_1_reg: [u8; 5],
-    channel_2_reg: [u8; 4],
-    channel_3_reg: [u8; 5],
-    channel_4_reg: [u8; 4],
-    wave_ram: [u8; 16],
+    channel_1: Square,
+    channel_2: Square,
+    channel_3: Wave,
+    channel_4: Noise,
     NR50: u8,
     NR51: u8,
     NR52: u8,
-    channel_1_buffer: [u8; SAMPLE_SIZE],
-    channel_2_buffer: [u8; SAMPLE_SIZE],
-    channel_3_buffer: [u8; SAMPLE_SIZE],
-    channel_4_buffer: [u8; SAMPLE_SIZE],
-} 
+    frame_step: u8,
+    last_div_bit: bool,
+    sample_clock: usize,
+    scope_pos: usize,
+    scope: [[u8; SCOPE_SIZE]; 4],
+    muted: [bool; 4],
+    soloed: [bool; 4],
+    mixer: Mixer,
+    recorder: Option<Recorder>,
+}
 
 impl APU {
     pub fn new() -> Self {
         Self {
-            channel_1_reg: [0; 5],
-            channel_2_reg: [0; 4],
-            channel_3_reg: [0; 5],
-            channel_4_reg: [0; 4],
-            wave_ram: [0; 16],
+            channel_1: Square::new(true),
+            channel_2: Square::new(false),
+            channel_3: Wave::new(),
+            channel_4: Noise::new(),
             NR50: 0,
             NR51: 0,
-            NR52: 0,
-            channel_1_buffer: [0; SAMPLE_SIZE],
-            channel_2_buffer: [0; SAMPLE_SIZE],
-            channel_3_buffer: [0; SAMPLE_SIZE],
-            channel_4_buffer: [0; SAMPLE_SIZE],
+            // the boot ROM leaves the APU powered on
+            NR52: 0x80,
+            frame_step: 0,
+            last_div_bit: false,
+            sample_clock: 0,
+            scope_pos: 0,
+            scope: [[0; SCOPE_SIZE]; 4],
+            muted: [false; 4],
+            soloed: [false; 4],
+            mixer: Mixer::new(DEFAULT_OUTPUT_RATE),
+            recorder: None,
         }
     }
 
+    fn powered(&self) -> bool {
+        self.NR52 & 0x80 != 0
+    }
+
     pub fn write(&mut self, addr: u16, value: u8) {
+        if !self.powered() {
+            // only wave RAM, NR52 and on the DMG the length counters stay writable
+            match addr {
+                0xFF11 => self.channel_1.load_length(value),
+                0xFF16 => self.channel_2.load_length(value),
+                0xFF1B => self.channel_3.load_length(value),
+                0xFF20 => self.channel_4.load_length(value),
+                0xFF26 => self.write_nr52(value),
+                0xFF30..=0xFF3F => self.channel_3.write_ram((addr - 0xFF30) as usize, value),
+                _ => {}
+            }
+            return;
+        }
+
         match addr {
-            0xFF10..=0xFF14 => self.channel_1_reg[(addr as usize) - 0xFF10] = value,
-            0xFF16..=0xFF19 => self.channel_2_reg[(addr as usize) - 0xFF16] = value,
-            0xFF1A..=0xFF1E => self.channel_3_reg[(addr as usize) - 0xFF1A] = value,
-            0xFF30..=0xFF3F => self.wave_ram [(addr as usize) - 0xFF30] = value,
-            0xFF20..=0xFF23 => self.channel_4_reg[(addr as usize) - 0xFF20] = value,
+            0xFF10..=0xFF14 => self.channel_1.write((addr - 0xFF10) as usize, value),
+            0xFF16..=0xFF19 => self.channel_2.write((addr - 0xFF15) as usize, value),
+            0xFF1A..=0xFF1E => self.channel_3.write((addr - 0xFF1A) as usize, value),
+            0xFF30..=0xFF3F => self.channel_3.write_ram((addr - 0xFF30) as usize, value),
+            0xFF20..=0xFF23 => self.channel_4.write((addr - 0xFF1F) as usize, value),
             0xFF24 => self.NR50 = value,
             0xFF25 => self.NR51 = value,
-            0xFF26 => self.NR52 = value,
+            0xFF26 => self.write_nr52(value),
+            0xFF15 | 0xFF1F | 0xFF27..=0xFF2F => {},
             _ => panic!("APU does not cover address range {}", addr)
         }
     }
 
+    // only the power bit is writable
+    fn write_nr52(&mut self, value: u8) {
+        let on = value & 0x80 != 0;
+        if self.powered() && !on {
+            self.channel_1.power_off();
+            self.channel_2.power_off();
+            self.channel_3.power_off();
+            self.channel_4.power_off();
+            self.NR50 = 0;
+            self.NR51 = 0;
+        }
+        if !self.powered() && on {
+            // the next frame sequencer step is 0 again and the waveforms start over
+            self.frame_step = 0;
+            self.channel_1.duty_pos = 0;
+            self.channel_2.duty_pos = 0;
+            self.channel_3.sample = 0;
+        }
+        self.NR52 = value & 0x80;
+    }
+
     pub fn read(&self, addr: u16) -> u8 {
-        match addr {
-            0xFF10..=0xFF14 => self.channel_1_reg[(addr as usize) - 0xFF10],
-            0xFF16..=0xFF19 => self.channel_2_reg[(addr as usize) - 0xFF16],
-            0xFF1A..=0xFF1E => self.channel_3_reg[(addr as usize) - 0xFF1A],
-            0xFF30..=0xFF3F => self.wave_ram [(addr as usize) - 0xFF30],
-            0xFF20..=0xFF23 => self.channel_4_reg[(addr as usize) - 0xFF20],
+        let raw = match addr {
+            0xFF10..=0xFF14 => self.channel_1.regs[(addr - 0xFF10) as usize],
+            0xFF16..=0xFF19 => self.channel_2.regs[(addr - 0xFF15) as usize],
+            0xFF1A..=0xFF1E => self.channel_3.regs[(addr - 0xFF1A) as usize],
+            0xFF30..=0xFF3F => return self.channel_3.read_ram((addr - 0xFF30) as usize),
+            0xFF20..=0xFF23 => self.channel_4.regs[(addr - 0xFF1F) as usize],
             0xFF24 => self.NR50,
             0xFF25 => self.NR51,
-            0xFF26 => self.NR52,
-            _ => 0xFF
+            0xFF26 => {
+                let active = self.channels_enabled().iter().enumerate()
+                    .fold(0, |bits, (i, on)| bits | (*on as u8) << i);
+                self.NR52 | active
+            },
+            _ => return 0xFF
+        };
+        raw | READ_MASKS[(addr - 0xFF10) as usize]
+    }
+
+    // muting and soloing only change what gets mixed, the channels keep running
+    pub fn set_muted(&mut self, channel: usize, muted: bool) {
+        if let Some(slot) = self.muted.get_mut(channel) {
+            *slot = muted;
+        }
+    }
+
+    // while any channel is soloed only soloed channels are mixed
+    pub fn set_soloed(&mut self, channel: usize, soloed: bool) {
+        if let Some(slot) = self.soloed.get_mut(channel) {
+            *slot = soloed;
+        }
+    }
+
+    fn is_audible(&self, channel: usize) -> bool {
+        let solo = self.soloed.iter().any(|soloed| *soloed);
+        !self.muted[channel] && (!solo || self.soloed[channel])
+    }
+
+    pub fn channel_status(&self, channel: usize) -> Option<ChannelStatus> {
+        let mut status = match channel {
+            0 => self.channel_1.status(),
+            1 => self.channel_2.status(),
+            2 => self.channel_3.status(),
+            3 => self.channel_4.status(),
+            _ => return None,
+        };
+        status.audible = self.is_audible(channel);
+        Some(status)
+    }
+
+    // the channel's recent digital output, oldest first
+    pub fn scope(&self, channel: usize) -> Vec<u8> {
+        match self.scope.get(channel) {
+            Some(window) => [&window[self.scope_pos..], &window[..self.scope_pos]].concat(),
+            None => vec![],
         }
     }
 
-    pub fn get_shared_buffer(&self) -> [&[u8;SAMPLE_SIZE]; 4] {
+    // digital output of each channel, 0-15
+    pub fn channel_outputs(&self) -> [u8; 4] {
+        [
+            self.channel_1.output(),
+            self.channel_2.output(),
+            self.channel_3.output(),
+            self.channel_4.output(),
+        ]
+    }
+
+    // what each DAC puts out, -1.0 to 1.0, None when the DAC is off
+    pub fn dac_levels(&self) -> [Option<f32>; 4] {
+        let dacs = [
+            self.channel_1.dac_enabled(),
+            self.channel_2.dac_enabled(),
+            self.channel_3.dac_enabled(),
+            self.channel_4.dac_enabled(),
+        ];
+        let outputs = self.channel_outputs();
+        let mut levels = [None; 4];
+        for i in 0..4 {
+            if dacs[i] {
+                levels[i] = Some(outputs[i] as f32 / 7.5 - 1.0);
+            }
+        }
+        levels
+    }
+
+    pub fn set_output_rate(&mut self, rate: u32) {
+        self.mixer = Mixer::new(rate);
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.mixer.output_rate()
+    }
+
+    // interleaved stereo samples at the output rate
+    pub fn drain_audio(&mut self, out: &mut [f32]) -> usize {
+        self.mixer.drain(out)
+    }
+
+    pub fn buffered_audio(&self) -> usize {
+        self.mixer.buffered()
+    }
+
+    // records at the current output rate, restarting drops what was recorded so far
+    pub fn start_recording(&mut self, per_channel: bool) {
+        self.recorder = Some(Recorder::new(self.output_rate(), per_channel));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recorder.take().map(Recorder::finish)
+    }
+
+    pub fn channels_enabled(&self) -> [bool; 4] {
         [
-            &self.channel_1_buffer,
-            &self.channel_2_buffer,
-            &self.channel_3_buffer,
-            &self.channel_4_buffer,
+            self.channel_1.enabled,
+            self.channel_2.enabled,
+            self.channel_3.enabled,
+            self.channel_4.enabled,
         ]
     }
 
-    fn buffer_channel_1(&mut self) {
-        let x: u32 = (self.channel_1_reg[3] as u32 & 3) << 8 | self.channel_1_reg[2] as u32;
-        let frequency = 131072 / ( 2048 - x );
-        for i in 0..self.channel_1_buffer.len() {
-            let value: u8 = if (i / (frequency as usize / 2)) % 2 == 0 {0} else {1};
-            self.channel_1_buffer[i] = value;
+    // 512 Hz, lengths on even steps, sweep on 2 and 6, envelopes on 7
+    fn step_frame_sequencer(&mut self) {
+        if self.frame_step & 1 == 0 {
+            self.channel_1.clock_length();
+            self.channel_2.clock_length();
+            self.channel_3.clock_length();
+            self.channel_4.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.channel_1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.channel_1.clock_envelope();
+            self.channel_2.clock_envelope();
+            self.channel_4.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    fn sample_scope(&mut self) {
+        let outputs = self.channel_outputs();
+        for (window, output) in self.scope.iter_mut().zip(outputs) {
+            window[self.scope_pos] = output;
+        }
+        self.scope_pos = (self.scope_pos + 1) % SCOPE_SIZE;
+    }
+
+    pub fn tick(&mut self, div: u16) {
+        let div_bit = div >> FRAME_SEQUENCER_BIT & 1 == 1;
+        if self.last_div_bit && !div_bit {
+            self.step_frame_sequencer();
         }
+        self.last_div_bit = div_bit;
+
+        self.channel_1.tick();
+        self.channel_2.tick();
+        self.channel_3.tick();
+        self.channel_4.tick();
+
+        let dacs = self.dac_levels();
+        let mut levels = dacs;
+        for (channel, level) in levels.iter_mut().enumerate() {
+            if !self.is_audible(channel) {
+                *level = None;
+            }
+        }
+        self.mixer.mix(levels, self.NR50, self.NR51);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(levels, dacs, self.NR50, self.NR51);
+        }
+
+        self.sample_clock += SCOPE_RATE;
+        if self.sample_clock >= M_CYCLES_PER_SECOND {
+            self.sample_clock -= M_CYCLES_PER_SECOND;
+            self.sample_scope();
+        }
+    }
+}
+
+impl Savable for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.volume);
+        state.u8(self.timer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.volume = state.u8()?;
+        self.timer = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Savable for Square {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.regs);
+        state.bool(self.enabled);
+        state.i32(self.timer);
+        state.u8(self.duty_pos);
+        state.u16(self.length);
+        self.envelope.save_state(state);
+        state.bool(self.sweep_enabled);
+        state.u8(self.sweep_timer);
+        state.u16(self.shadow);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        state.bytes(&mut self.regs)?;
+        self.enabled = state.bool()?;
+        self.timer = state.i32()?;
+        self.duty_pos = state.u8()? % 8;
+        self.length = state.u16()?;
+        self.envelope.load_state(state)?;
+        self.sweep_enabled = state.bool()?;
+        self.sweep_timer = state.u8()?;
+        self.shadow = state.u16()?;
+        Ok(())
+    }
+}
+
+impl Savable for Wave {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.regs);
+        state.bytes(&self.ram);
+        state.bool(self.enabled);
+        state.i32(self.timer);
+        state.u8(self.position);
+        state.u8(self.sample);
+        state.bool(self.accessed);
+        state.u16(self.length);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        state.bytes(&mut self.regs)?;
+        state.bytes(&mut self.ram)?;
+        self.enabled = state.bool()?;
+        self.timer = state.i32()?;
+        self.position = state.u8()? % 32;
+        self.sample = state.u8()?;
+        self.accessed = state.bool()?;
+        self.length = state.u16()?;
+        Ok(())
+    }
+}
+
+impl Savable for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.regs);
+        state.bool(self.enabled);
+        state.i32(self.timer);
+        state.u16(self.lfsr);
+        state.u16(self.length);
+        self.envelope.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        state.bytes(&mut self.regs)?;
+        self.enabled = state.bool()?;
+        self.timer = state.i32()?;
+        self.lfsr = state.u16()?;
+        self.length = state.u16()?;
+        self.envelope.load_state(state)
+    }
+}
+
+// the scopes, mixer and recorder are output only, mute and solo belong to
+// whoever is listening
+impl Savable for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.channel_1.save_state(state);
+        self.channel_2.save_state(state);
+        self.channel_3.save_state(state);
+        self.channel_4.save_state(state);
+        state.u8(self.NR50);
+        state.u8(self.NR51);
+        state.u8(self.NR52);
+        state.u8(self.frame_step);
+        state.bool(self.last_div_bit);
+        state.u32(self.sample_clock as u32);
     }
 
-    pub fn tick(&mut self) {
-        // populate channels;
-        // self.buffer_channel_1();
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.channel_1.load_state(state)?;
+        self.channel_2.load_state(state)?;
+        self.channel_3.load_state(state)?;
+        self.channel_4.load_state(state)?;
+        self.NR50 = state.u8()?;
+        self.NR51 = state.u8()?;
+        self.NR52 = state.u8()?;
+        self.frame_step = state.u8()? % 8;
+        self.last_div_bit = state.bool()?;
+        self.sample_clock = state.u32()? as usize;
+        Ok(())
     }
-}
\ No newline at end of file
+}
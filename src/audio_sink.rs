@@ -0,0 +1,228 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use js_sys::{Atomics, Float32Array, Int32Array, SharedArrayBuffer};
+use wasm_bindgen::prelude::*;
+
+use crate::Emu;
+
+// control words in front of the samples, www/audio-worklet.js uses the same layout
+pub const READ_INDEX: u32 = 0;
+pub const WRITE_INDEX: u32 = 1;
+pub const UNDERRUNS: u32 = 2;
+pub const OVERRUNS: u32 = 3;
+const CONTROL_WORDS: u32 = 4;
+
+const FEED_CHUNK: usize = 1024;
+
+// Single producer, single consumer ring of interleaved stereo samples. Both
+// indices only ever move by whole frames and one frame is always left empty,
+// so a full ring can be told apart from an empty one.
+pub trait SampleRing {
+    fn capacity(&self) -> u32;
+    fn load(&self, word: u32) -> u32;
+    fn store(&self, word: u32, value: u32);
+    fn add(&self, word: u32, value: u32);
+    fn write_samples(&self, offset: u32, samples: &[f32]);
+    fn read_samples(&self, offset: u32, out: &mut [f32]);
+
+    // floats waiting for the consumer
+    fn available(&self) -> u32 {
+        let capacity = self.capacity();
+        (self.load(WRITE_INDEX) + capacity - self.load(READ_INDEX)) % capacity
+    }
+
+    fn underruns(&self) -> u32 {
+        self.load(UNDERRUNS)
+    }
+
+    fn overruns(&self) -> u32 {
+        self.load(OVERRUNS)
+    }
+
+    // producer side, whatever does not fit is dropped and counted as an overrun
+    fn push(&self, samples: &[f32]) -> usize {
+        let capacity = self.capacity();
+        let write = self.load(WRITE_INDEX);
+        let free = capacity - 1 - self.available();
+        let count = (free as usize).min(samples.len()) & !1;
+        if count < samples.len() {
+            self.add(OVERRUNS, 1);
+        }
+
+        let first = count.min((capacity - write) as usize);
+        self.write_samples(write, &samples[..first]);
+        self.write_samples(0, &samples[first..count]);
+        self.store(WRITE_INDEX, (write + count as u32) % capacity);
+        count
+    }
+
+    // consumer side, a short read is padded with silence and counted as an underrun
+    fn pop(&self, out: &mut [f32]) -> usize {
+        let capacity = self.capacity();
+        let read = self.load(READ_INDEX);
+        let count = (self.available() as usize).min(out.len()) & !1;
+        if count < out.len() {
+            self.add(UNDERRUNS, 1);
+        }
+
+        let first = count.min((capacity - read) as usize);
+        self.read_samples(read, &mut out[..first]);
+        self.read_samples(0, &mut out[first..count]);
+        out[count..].iter_mut().for_each(|sample| *sample = 0.0);
+        self.store(READ_INDEX, (read + count as u32) % capacity);
+        count
+    }
+}
+
+// rounded up to whole frames, plus a frame for the empty slot
+fn ring_capacity(capacity: u32) -> u32 {
+    ((capacity.max(2) + 1) & !1) + 2
+}
+
+// ring in plain memory, for native frontends
+pub struct MemoryRing {
+    control: [AtomicU32; CONTROL_WORDS as usize],
+    samples: Vec<AtomicU32>,
+}
+
+impl MemoryRing {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            control: Default::default(),
+            samples: (0..ring_capacity(capacity)).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+impl SampleRing for MemoryRing {
+    fn capacity(&self) -> u32 {
+        self.samples.len() as u32
+    }
+
+    fn load(&self, word: u32) -> u32 {
+        self.control[word as usize].load(Ordering::Acquire)
+    }
+
+    fn store(&self, word: u32, value: u32) {
+        self.control[word as usize].store(value, Ordering::Release);
+    }
+
+    fn add(&self, word: u32, value: u32) {
+        self.control[word as usize].fetch_add(value, Ordering::AcqRel);
+    }
+
+    fn write_samples(&self, offset: u32, samples: &[f32]) {
+        for (slot, sample) in self.samples[offset as usize..].iter().zip(samples) {
+            slot.store(sample.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    fn read_samples(&self, offset: u32, out: &mut [f32]) {
+        for (sample, slot) in out.iter_mut().zip(&self.samples[offset as usize..]) {
+            *sample = f32::from_bits(slot.load(Ordering::Relaxed));
+        }
+    }
+}
+
+// ring in a SharedArrayBuffer that an AudioWorklet reads from
+pub struct SharedRing {
+    buffer: SharedArrayBuffer,
+    control: Int32Array,
+    samples: Float32Array,
+    capacity: u32,
+}
+
+impl SharedRing {
+    pub fn new(capacity: u32) -> Self {
+        let capacity = ring_capacity(capacity);
+        let buffer = SharedArrayBuffer::new((CONTROL_WORDS + capacity) * 4);
+        Self {
+            control: Int32Array::new_with_byte_offset_and_length(&buffer, 0, CONTROL_WORDS),
+            samples: Float32Array::new_with_byte_offset_and_length(&buffer, CONTROL_WORDS * 4, capacity),
+            buffer,
+            capacity,
+        }
+    }
+}
+
+impl SampleRing for SharedRing {
+    fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    fn load(&self, word: u32) -> u32 {
+        Atomics::load(&self.control, word).unwrap_throw() as u32
+    }
+
+    fn store(&self, word: u32, value: u32) {
+        Atomics::store(&self.control, word, value as i32).unwrap_throw();
+    }
+
+    fn add(&self, word: u32, value: u32) {
+        Atomics::add(&self.control, word, value as i32).unwrap_throw();
+    }
+
+    fn write_samples(&self, offset: u32, samples: &[f32]) {
+        if !samples.is_empty() {
+            self.samples.subarray(offset, offset + samples.len() as u32).copy_from(samples);
+        }
+    }
+
+    fn read_samples(&self, offset: u32, out: &mut [f32]) {
+        if !out.is_empty() {
+            self.samples.subarray(offset, offset + out.len() as u32).copy_to(out);
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct AudioSink {
+    ring: SharedRing,
+    chunk: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl AudioSink {
+    // `capacity` is in floats, two per stereo frame
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            ring: SharedRing::new(capacity),
+            chunk: vec![0.0; FEED_CHUNK],
+        }
+    }
+
+    // hand this to the worklet, it holds the control words and the samples
+    pub fn buffer(&self) -> SharedArrayBuffer {
+        self.ring.buffer.clone()
+    }
+
+    // moves everything the emulator mixed into the ring, returns how many floats made it
+    pub fn feed(&mut self, emu: &mut Emu) -> usize {
+        let mut written = 0;
+        let mut overrun = false;
+        loop {
+            let count = emu.drain_audio(&mut self.chunk);
+            if count == 0 {
+                return written;
+            }
+            // once the ring is full the rest of this batch is thrown away
+            if !overrun {
+                let pushed = self.ring.push(&self.chunk[..count]);
+                overrun = pushed < count;
+                written += pushed;
+            }
+        }
+    }
+
+    pub fn buffered(&self) -> u32 {
+        self.ring.available()
+    }
+
+    pub fn underruns(&self) -> u32 {
+        self.ring.underruns()
+    }
+
+    pub fn overruns(&self) -> u32 {
+        self.ring.overruns()
+    }
+}
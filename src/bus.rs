@@ -0,0 +1,11 @@
+// everything the CPU can see of the address space, the emulator hands it Mem
+// while the single step tests run it against a flat 64 KiB array
+pub trait Bus {
+    // a read the CPU makes on the current M-cycle
+    fn get(&self, addr: u16) -> u8;
+    fn set(&mut self, addr: u16, value: u8);
+    // looks at a value without it showing up as a bus access, for the interrupt lines and debugging
+    fn peek(&self, addr: u16) -> u8;
+    // for STOP, DIV goes back to 0 without a write to it
+    fn reset_div(&mut self);
+}
@@ -0,0 +1,143 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::EmuError;
+use crate::mbc::{MBCBuilder, NINTENDO_LOGO};
+
+const LOGO_ADDR: usize = 0x0104;
+const TITLE_ADDR: usize = 0x0134;
+const MANUFACTURER_ADDR: usize = 0x013F;
+const CGB_FLAG_ADDR: usize = 0x0143;
+const NEW_LICENSEE_ADDR: usize = 0x0144;
+const SGB_FLAG_ADDR: usize = 0x0146;
+const TYPE_ADDR: usize = 0x0147;
+const ROM_SIZE_ADDR: usize = 0x0148;
+const RAM_SIZE_ADDR: usize = 0x0149;
+const OLD_LICENSEE_ADDR: usize = 0x014B;
+const VERSION_ADDR: usize = 0x014C;
+const HEADER_CHECKSUM_ADDR: usize = 0x014D;
+const GLOBAL_CHECKSUM_ADDR: usize = 0x014E;
+
+pub const HEADER_END: usize = 0x0150;
+
+// old licensee 0x33 means the publisher is in the new licensee field
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CgbSupport {
+    None,
+    Enhanced,
+    Only,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct CartridgeInfo {
+    title: String,
+    manufacturer_code: String,
+    new_licensee_code: String,
+    pub old_licensee_code: u8,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub cartridge_type: u8,
+    pub rom_size: u32,
+    pub ram_size: u32,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub header_checksum_valid: bool,
+    pub global_checksum_valid: bool,
+    pub logo_valid: bool,
+}
+
+impl CartridgeInfo {
+    pub fn from_rom(rom: &[u8]) -> Result<Self, EmuError> {
+        if rom.len() < HEADER_END {
+            return Err(EmuError::TruncatedRom { expected: HEADER_END, actual: rom.len() });
+        }
+
+        let cgb = match rom[CGB_FLAG_ADDR] {
+            0x80 => CgbSupport::Enhanced,
+            0xC0 => CgbSupport::Only,
+            _ => CgbSupport::None,
+        };
+
+        // later carts took the tail of the title for the manufacturer code and CGB flag
+        let manufacturer = &rom[MANUFACTURER_ADDR..CGB_FLAG_ADDR];
+        let has_manufacturer = cgb != CgbSupport::None
+            && manufacturer.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        let title_end = if has_manufacturer {
+            MANUFACTURER_ADDR
+        } else if cgb != CgbSupport::None {
+            CGB_FLAG_ADDR
+        } else {
+            NEW_LICENSEE_ADDR
+        };
+
+        let header_checksum = rom[HEADER_CHECKSUM_ADDR];
+        let global_checksum = (rom[GLOBAL_CHECKSUM_ADDR] as u16) << 8 | rom[GLOBAL_CHECKSUM_ADDR + 1] as u16;
+        let rom_banks = MBCBuilder::get_rom_banks_from_header(rom[ROM_SIZE_ADDR]);
+        let (ram_total, _) = MBCBuilder::get_ram_size_from_header(rom[RAM_SIZE_ADDR])?;
+
+        Ok(Self {
+            title: CartridgeInfo::ascii(&rom[TITLE_ADDR..title_end]),
+            manufacturer_code: if has_manufacturer { CartridgeInfo::ascii(manufacturer) } else { String::new() },
+            new_licensee_code: CartridgeInfo::ascii(&rom[NEW_LICENSEE_ADDR..SGB_FLAG_ADDR]),
+            old_licensee_code: rom[OLD_LICENSEE_ADDR],
+            cgb,
+            // SGB functions are only enabled alongside the new licensee marker
+            sgb: rom[SGB_FLAG_ADDR] == 0x03 && rom[OLD_LICENSEE_ADDR] == USE_NEW_LICENSEE,
+            cartridge_type: rom[TYPE_ADDR],
+            rom_size: rom_banks as u32 * 16 * 1024,
+            ram_size: ram_total as u32 * 1024,
+            version: rom[VERSION_ADDR],
+            header_checksum,
+            global_checksum,
+            header_checksum_valid: CartridgeInfo::compute_header_checksum(rom) == header_checksum,
+            global_checksum_valid: CartridgeInfo::compute_global_checksum(rom) == global_checksum,
+            logo_valid: rom[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()] == NINTENDO_LOGO,
+        })
+    }
+
+    fn ascii(bytes: &[u8]) -> String {
+        bytes.iter()
+            .take_while(|c| **c != 0)
+            .map(|c| if c.is_ascii_graphic() || *c == b' ' { *c as char } else { '?' })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    // the boot rom refuses to start when this doesn't match
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[TITLE_ADDR..HEADER_CHECKSUM_ADDR]
+            .iter()
+            .fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1))
+    }
+
+    // not checked by hardware, sums every byte except the checksum itself
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != GLOBAL_CHECKSUM_ADDR && *i != GLOBAL_CHECKSUM_ADDR + 1)
+            .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+    }
+}
+
+#[wasm_bindgen]
+impl CartridgeInfo {
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn manufacturer_code(&self) -> String {
+        self.manufacturer_code.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn new_licensee_code(&self) -> String {
+        self.new_licensee_code.clone()
+    }
+}
@@ -1,4 +1,7 @@
-use crate::mem::Mem;
+use crate::error::EmuError;
+use crate::io::P1_ADDR;
+use crate::bus::Bus;
+use crate::state::{Savable, StateReader, StateWriter};
 use wasm_bindgen::prelude::*;
 
 extern crate web_sys;
@@ -50,6 +53,14 @@ pub enum Flag {
     C
 }
 
+// an undefined opcode locked up the CPU, only a reset gets it going again
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuFault {
+    pub pc: u16,
+    pub opcode: u8,
+}
+
 #[wasm_bindgen]
 pub struct Cpu {
     // registers
@@ -67,9 +78,17 @@ pub struct Cpu {
 
     pub is_halt: bool,
     pub is_stop: bool,
+    // HALT exited straight away, the next opcode fetch does not move PC
+    halt_bug: bool,
+    // EI was executed, IME is set once the next instruction starts
+    pub ei_pending: bool,
+    // the current cycles belong to an interrupt dispatch instead of current_op
+    pub dispatching: bool,
+    fault: Option<CpuFault>,
 }
 
 #[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct DebugCpu {
     pub AF: u16, 
     pub BC: u16, 
@@ -82,6 +101,50 @@ pub struct DebugCpu {
     pub stop: bool
 }
 
+impl Savable for Cpu {
+    fn save_state(&self, state: &mut StateWriter) {
+        for reg in [self.AF, self.BC, self.DE, self.HL, self.SP, self.PC] {
+            state.u16(reg);
+        }
+        state.bool(self.ime);
+        // mid instruction progress so a state can be taken on any tick
+        state.i32(self.current_cycle);
+        state.u8(self.current_op);
+        state.bytes(&self.store);
+        state.bool(self.is_halt);
+        state.bool(self.is_stop);
+        state.bool(self.halt_bug);
+        state.bool(self.ei_pending);
+        state.bool(self.dispatching);
+        state.bool(self.fault.is_some());
+        let fault = self.fault.unwrap_or(CpuFault { pc: 0, opcode: 0 });
+        state.u16(fault.pc);
+        state.u8(fault.opcode);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.AF = state.u16()?;
+        self.BC = state.u16()?;
+        self.DE = state.u16()?;
+        self.HL = state.u16()?;
+        self.SP = state.u16()?;
+        self.PC = state.u16()?;
+        self.ime = state.bool()?;
+        self.current_cycle = state.i32()?;
+        self.current_op = state.u8()?;
+        state.bytes(&mut self.store)?;
+        self.is_halt = state.bool()?;
+        self.is_stop = state.bool()?;
+        self.halt_bug = state.bool()?;
+        self.ei_pending = state.bool()?;
+        self.dispatching = state.bool()?;
+        let locked = state.bool()?;
+        let fault = CpuFault { pc: state.u16()?, opcode: state.u8()? };
+        self.fault = if locked { Some(fault) } else { None };
+        Ok(())
+    }
+}
+
 impl Cpu {
     pub fn new() -> Self {
         Self {
@@ -96,6 +159,10 @@ impl Cpu {
             store: [0; 5],
             is_halt: false,
             is_stop: false,
+            halt_bug: false,
+            ei_pending: false,
+            dispatching: false,
+            fault: None,
             ime: false
         }
     }
@@ -114,9 +181,13 @@ impl Cpu {
         }
     }
 
-    pub fn get_op(&mut self, mem: &Mem) -> u8 {
+    pub fn get_op(&mut self, mem: &dyn Bus) -> u8 {
         let pc = self.PC;
-        self.PC += 1;
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.PC = self.PC.wrapping_add(1);
+        }
         mem.get(pc)
     }
 
@@ -128,63 +199,105 @@ impl Cpu {
         self.current_cycle.clone()
     }
 
-    pub fn interupt(&mut self, mem: &mut Mem, isr: u16) {
-        let pc = self.PC;
-        mem.set(self.SP - 1, (pc >> 8) as u8);
-        mem.set(self.SP - 2, (pc) as u8);
-        self.SP -= 2;
-        self.PC = isr;
+    pub fn fault(&self) -> Option<CpuFault> {
+        self.fault
     }
 
-    pub fn check_interupts(&mut self, mem: &mut Mem) {
-        // check for interupts
-        if !self.ime {return}
+    // IE and IF are wired straight into the CPU, checking them is not a bus read
+    pub fn interupt_pending(&self, mem: &dyn Bus) -> bool {
+        mem.peek(0xFFFF) & mem.peek(0xFF0F) & 0x1F != 0
+    }
 
-        let enabled = mem.get(0xFFFF); // is the interupt enabled
-        let flags = mem.get(0xFF0F); // was the interupt triggered
+    fn at_boundary(&self) -> bool {
+        self.current_cycle == 1 && self.fault.is_none() && !self.is_stop && !self.is_halt
+    }
 
-        if flags & 1 == 1 && enabled & 1 == 1 { // vblank
-            self.interupt(mem, 0x40);
-            self.ime = false;
-            mem.set(0xFF0F, flags & !1);
+    // the system samples the interrupt lines between instructions, tick on its own never dispatches
+    pub fn check_interupts(&mut self, mem: &dyn Bus) {
+        if self.at_boundary() {
+            self.dispatching = self.ime && self.interupt_pending(mem);
+        }
+    }
 
-        } else if flags & 2 == 2 && enabled & 2 == 2 { // lcd stat
-            self.interupt(mem, 0x48);
-            self.ime = false;
-            mem.set(0xFF0F, flags & !2);
+    // the next tick fetches an opcode rather than waiting or servicing an interrupt
+    pub fn fetching(&self) -> bool {
+        self.at_boundary() && !self.dispatching
+    }
 
-        } else if flags & 4 == 4 && enabled & 4 == 4 { // timer
-            self.interupt(mem, 0x50);
-            self.ime = false;
-            mem.set(0xFF0F, flags & !4);
+    // 2 idle cycles, PC pushed high byte first, then the jump
+    fn dispatch(&mut self, mem: &mut dyn Bus) {
+        match self.current_cycle {
+            3 => {
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, (self.PC >> 8) as u8);
+            },
+            4 => {
+                // the vector is picked after the high byte push, which may have overwritten IE
+                let pending = mem.peek(0xFFFF) & mem.peek(0xFF0F) & 0x1F;
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, self.PC as u8);
+                if pending == 0 {
+                    // nothing left to service, the CPU ends up at 0x0000
+                    self.store[0] = 0;
+                } else {
+                    let bit = pending.trailing_zeros() as u8;
+                    self.store[0] = 0x40 + bit * 8;
+                    mem.set(0xFF0F, mem.peek(0xFF0F) & !(1 << bit));
+                }
+            },
+            5 => {
+                self.PC = self.store[0] as u16;
+                self.reset();
+            },
+            _ => ()
+        }
+    }
 
-        } else if flags & 8 == 8 && enabled & 8 == 8 { // serial
-            self.interupt(mem, 0x58);
-            self.ime = false;
-            mem.set(0xFF0F, flags & !8);
+    pub fn tick(&mut self, mem: &mut dyn Bus) {
+        // locked up for good, not even interrupts are serviced
+        if self.fault.is_some() {
+            return;
+        }
 
-        } else if flags & 16 == 16 && enabled & 16 == 16 { // joypad
-            self.interupt(mem, 0x60);
-            self.ime = false;
-            mem.set(0xFF0F, flags & !16);
+        if self.is_stop {
+            // the clock only restarts once a joypad line goes low
+            if mem.peek(P1_ADDR) & 0x0F != 0x0F {
+                self.is_stop = false;
+            }
+            return;
+        }
+
+        if self.is_halt {
+            // waking up takes a cycle, without IME the next instruction just runs
+            if self.interupt_pending(mem) {
+                self.is_halt = false;
+            }
+            return;
         }
-    }
 
-    pub fn tick(&mut self, mem: &mut Mem) {
-        
         match self.current_cycle { 
-            1 => { // start decoding new op
-                self.current_op = self.get_op(mem);
-                self.execute(mem);
+            1 => { // start decoding new op, or service an interrupt instead
+                if self.dispatching {
+                    self.ime = false;
+                    self.dispatch(mem);
+                } else {
+                    // EI lands here, after the check for the instruction following it
+                    if self.ei_pending {
+                        self.ei_pending = false;
+                        self.ime = true;
+                    }
+                    self.current_op = self.get_op(mem);
+                    self.execute(mem);
+                }
+            },
+            _ if self.dispatching => {
+                self.dispatch(mem);
             },
             _ => { // continue executing
                 self.execute(mem);
             }
         }
 
-        if self.current_cycle == 0 { // finished execution
-            self.check_interupts(mem); 
-        }
         self.current_cycle += 1;
     }
 
@@ -334,7 +447,7 @@ impl Cpu {
         return false;
     }
 
-    pub fn execute(&mut self, mem: &mut Mem) {
+    pub fn execute(&mut self, mem: &mut dyn Bus) {
         match self.current_op {
             0x00 => self.noop(),
             0x01 => self.ld_rr_d16(mem, &Reg::BC),
@@ -353,7 +466,7 @@ impl Cpu {
             0x0E => self.ld_r_d8(mem, &HalfReg::C),
             0x0F => self.rrca(),
 
-            0x10 => self.stop(),
+            0x10 => self.stop(mem),
             0x11 => self.ld_rr_d16(mem, &Reg::DE),
             0x12 => self.ld_ar_a(&Reg::DE, mem),
             0x13 => self.inc_rr(&Reg::DE),
@@ -461,7 +574,7 @@ impl Cpu {
             0x73 => self.ld_hl_r(mem, &HalfReg::E),
             0x74 => self.ld_hl_r(mem, &HalfReg::H),
             0x75 => self.ld_hl_r(mem, &HalfReg::L),
-            0x76 => self.halt(),
+            0x76 => self.halt(mem),
             0x77 => self.ld_hl_r(mem, &HalfReg::A),
             0x78 => self.ld_r_r( &HalfReg::A, &HalfReg::B),
             0x79 => self.ld_r_r( &HalfReg::A, &HalfReg::C),
@@ -597,7 +710,8 @@ impl Cpu {
             0xFE => self.cp_d8(mem),
             0xFF => self.rst(mem, 0x38),
 
-            _ => ()
+            // 0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD
+            _ => self.lockup()
         }
     }
 
@@ -609,28 +723,47 @@ impl Cpu {
         self.reset();
     }
 
-    fn stop(&mut self) {
+    fn lockup(&mut self) {
+        self.fault = Some(CpuFault {
+            pc: self.PC.wrapping_sub(1),
+            opcode: self.current_op,
+        });
+        self.reset();
+    }
+
+    fn stop(&mut self, mem: &mut dyn Bus) {
+        // the byte after STOP is skipped
+        self.PC = self.PC.wrapping_add(1);
+        mem.reset_div();
         self.is_stop = true;
         self.reset();
     }
-    fn halt(&mut self) {
-        self.is_halt = true;
+
+    fn halt(&mut self, mem: &dyn Bus) {
+        if !self.interupt_pending(mem) {
+            self.is_halt = true;
+        } else if !self.ime {
+            // with an interrupt already pending and IME off HALT falls through and the next byte is read twice
+            self.halt_bug = true;
+        }
         self.reset();
     }
 
-    fn di(&mut self, mem: &mut Mem) {
+    fn di(&mut self, mem: &mut dyn Bus) {
         self.ime = false;
+        self.ei_pending = false;
         self.reset();
     }
 
-    fn ei(&mut self, mem: &mut Mem) {
-        self.ime = true;
+    // IME is only set after the next instruction, so EI; RET can't be interrupted
+    fn ei(&mut self, mem: &mut dyn Bus) {
+        self.ei_pending = true;
         self.reset();
     }
 
     // LOAD
 
-    pub fn ld_rr_d16(&mut self, mem: &Mem, reg: &Reg) {
+    pub fn ld_rr_d16(&mut self, mem: &dyn Bus, reg: &Reg) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -652,7 +785,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn ld_r_hl(&mut self, to: &HalfReg, mem: &Mem, ) {
+    fn ld_r_hl(&mut self, to: &HalfReg, mem: &dyn Bus, ) {
         match self.current_cycle {
             2 => {
                 self.set_byte_reg(
@@ -665,7 +798,7 @@ impl Cpu {
         }
     }
 
-    fn ld_r_d8(&mut self,  mem: &Mem, to: &HalfReg) {
+    fn ld_r_d8(&mut self,  mem: &dyn Bus, to: &HalfReg) {
         match self.current_cycle {
             2 => {
                 let value = self.get_op(mem);
@@ -676,33 +809,33 @@ impl Cpu {
         }
     }
 
-    pub fn ld_a_hl_inc(&mut self, mem: &Mem) {
+    pub fn ld_a_hl_inc(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let addr = self.HL;
                 let value = mem.get(addr);
                 self.set_byte_reg(&HalfReg::A, value);
-                self.HL += 1;
+                self.HL = self.HL.wrapping_add(1);
                 self.reset();
             },
             _ => ()
         }
     }
 
-    pub fn ld_a_hl_dec(&mut self, mem: &Mem) {
+    pub fn ld_a_hl_dec(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let addr = self.HL;
                 let value = mem.get(addr);
                 self.set_byte_reg(&HalfReg::A, value);
-                self.HL -= 1;
+                self.HL = self.HL.wrapping_sub(1);
                 self.reset();
             },
             _ => (),
         }
     }
 
-    pub fn ld_ar_a(&mut self, to_reg: &Reg, mem: &mut Mem) {
+    pub fn ld_ar_a(&mut self, to_reg: &Reg, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 let value = self.get_byte_reg(&HalfReg::A);
@@ -714,7 +847,7 @@ impl Cpu {
         }
     }
 
-    fn ld_a16_sp(&mut self, mem: &mut Mem) {
+    fn ld_a16_sp(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => self.store[0] = self.get_op(mem),
             3 => self.store[1] = self.get_op(mem),
@@ -724,14 +857,14 @@ impl Cpu {
             },
             5 => {
                 let addr = (self.store[1] as u16) << 8 | (self.store[0] as u16);
-                mem.set(addr + 1, (self.SP >> 8) as u8);
+                mem.set(addr.wrapping_add(1), (self.SP >> 8) as u8);
                 self.reset();
             },
             _ => ()
         }
     }
 
-    fn ld_d8_a(&mut self, mem: &mut Mem) {
+    fn ld_d8_a(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -747,7 +880,7 @@ impl Cpu {
         }
     }
 
-    fn ld_a_d8(&mut self, mem: &mut Mem) {
+    fn ld_a_d8(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -763,7 +896,7 @@ impl Cpu {
         }
     }
 
-    fn ld_c_a(&mut self, mem: &mut Mem) {
+    fn ld_c_a(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 mem.set(
@@ -776,7 +909,7 @@ impl Cpu {
         }
     }
 
-    fn ld_a_c(&mut self, mem: &mut Mem) {
+    fn ld_a_c(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.set_byte_reg(
@@ -790,7 +923,7 @@ impl Cpu {
     }
 
     
-    fn ld_a_rr(&mut self, mem: &Mem, from: &Reg) {
+    fn ld_a_rr(&mut self, mem: &dyn Bus, from: &Reg) {
         match self.current_cycle {
             2 => {
                 let addr = self.get_word_reg(from);
@@ -802,33 +935,33 @@ impl Cpu {
         }
     }
 
-    fn ld_hl_inc_a(&mut self, mem: &mut Mem) {
+    fn ld_hl_inc_a(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 let addr = self.HL;
                 let value = self.get_byte_reg(&HalfReg::A);
                 mem.set(addr, value);
-                self.HL += 1;
+                self.HL = self.HL.wrapping_add(1);
                 self.reset();
             },
             _ => ()
         }
     }
 
-    fn ld_hl_dec_a(&mut self, mem: &mut Mem) {
+    fn ld_hl_dec_a(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 let addr = self.HL;
                 let value = self.get_byte_reg(&HalfReg::A);
                 mem.set(addr, value);
-                self.HL -= 1;
+                self.HL = self.HL.wrapping_sub(1);
                 self.reset();
             },
             _ => ()
         }
     }
 
-    fn ld_hl_d8(&mut self, mem: &mut Mem) {
+    fn ld_hl_d8(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem); // d8
@@ -841,7 +974,7 @@ impl Cpu {
         }
     }
 
-    fn ld_hl_r(&mut self, mem: &mut Mem, from: &HalfReg) {
+    fn ld_hl_r(&mut self, mem: &mut dyn Bus, from: &HalfReg) {
         match self.current_cycle {
             2 => {
                 let value = self.get_byte_reg(from);
@@ -852,7 +985,7 @@ impl Cpu {
         }
     }
 
-    fn ld_a16_a(&mut self, mem: &mut Mem) {
+    fn ld_a16_a(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -871,7 +1004,7 @@ impl Cpu {
         }
     }
 
-    fn ld_a_a16(&mut self, mem: &Mem) {
+    fn ld_a_a16(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -890,7 +1023,7 @@ impl Cpu {
         }
     }
 
-    fn ld_hl_sp_s8(&mut self, mem: &Mem) {
+    fn ld_hl_sp_s8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -969,7 +1102,7 @@ impl Cpu {
         self.reset();
     }
 
-    pub fn inc_hl(&mut self, mem: &mut Mem) {
+    pub fn inc_hl(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = mem.get(self.HL).overflowing_add(1).0;
@@ -985,7 +1118,7 @@ impl Cpu {
         }
     }
 
-    pub fn dec_hl(&mut self, mem: &mut Mem) {
+    pub fn dec_hl(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = mem.get(self.HL).overflowing_sub(1).0;
@@ -1103,20 +1236,14 @@ impl Cpu {
 
     // JUMP
 
-    fn jr(&mut self, mem: &Mem) {
+    fn jr(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
             },
             3 => {
-                let mut pc = self.PC;
-                let rel = self.store[0] as i8;
-                if rel < 0 {
-                    pc -= rel.abs() as u16;
-                } else {
-                    pc += rel as u16;
-                }
-                self.PC = pc;
+                // sign extended, so adding it wraps to the right address either way
+                self.PC = self.PC.wrapping_add(self.store[0] as i8 as u16);
                 self.reset();
             },
             _ => (),
@@ -1124,7 +1251,7 @@ impl Cpu {
 
     }
 
-    fn jr_con(&mut self, mem: &Mem, con: Flag, value: bool) {
+    fn jr_con(&mut self, mem: &dyn Bus, con: Flag, value: bool) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem); // s8
@@ -1134,21 +1261,15 @@ impl Cpu {
                 }
             },
             3 => {
-                let mut pc = self.PC;
-                let rel = self.store[0] as i8;
-                if rel < 0 {
-                    pc -= rel.abs() as u16;
-                } else {
-                    pc += rel as u16;
-                }
-                self.PC = pc;
+                // sign extended, so adding it wraps to the right address either way
+                self.PC = self.PC.wrapping_add(self.store[0] as i8 as u16);
                 self.reset();
             },
             _ => ()
         }
     } 
 
-    fn jp(&mut self, mem: &Mem) {
+    fn jp(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -1164,7 +1285,7 @@ impl Cpu {
         }
     }
 
-    fn jp_con(&mut self, mem: &Mem, flag: Flag, check: bool) {
+    fn jp_con(&mut self, mem: &dyn Bus, flag: Flag, check: bool) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -1221,28 +1342,23 @@ impl Cpu {
     }
 
     fn addc_a(&mut self, from: &HalfReg) {
-        match self.current_cycle {
-            2 => {
-                let a = self.get_byte_reg(&HalfReg::A);
-                let b = self.get_byte_reg(from);
-                let c = if self.get_flag(Flag::C) {1} else {0};
-                let (result1, overflow1) = a.overflowing_add(b);
-                let (result, overflow2) = result1.overflowing_add(c);
-                let hc1 = self.set_bhca(a, b);
-                let hc2 = self.set_bhca(result1, c);
-                self.set_flag(Flag::C, overflow1 || overflow2);
-                self.set_flag(Flag::Z, result == 0);
-                self.set_flag(Flag::N, false);
-                self.set_flag(Flag::H, hc1 || hc2);
-                
-                self.set_byte_reg(&HalfReg::A, result);
-                self.reset();
-            },
-            _ => {},
-        }        
+        let a = self.get_byte_reg(&HalfReg::A);
+        let b = self.get_byte_reg(from);
+        let c = if self.get_flag(Flag::C) {1} else {0};
+        let (result1, overflow1) = a.overflowing_add(b);
+        let (result, overflow2) = result1.overflowing_add(c);
+        let hc1 = self.set_bhca(a, b);
+        let hc2 = self.set_bhca(result1, c);
+        self.set_flag(Flag::C, overflow1 || overflow2);
+        self.set_flag(Flag::Z, result == 0);
+        self.set_flag(Flag::N, false);
+        self.set_flag(Flag::H, hc1 || hc2);
+
+        self.set_byte_reg(&HalfReg::A, result);
+        self.reset();
     }
 
-    fn add_m(&mut self, mem: &Mem) {
+    fn add_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1260,7 +1376,7 @@ impl Cpu {
         }
     }
 
-    fn add_d8(&mut self, mem: &Mem) {
+    fn add_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1278,7 +1394,7 @@ impl Cpu {
         }
     }
 
-    fn add_sp_s8(&mut self, mem: &Mem) {
+    fn add_sp_s8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -1302,7 +1418,7 @@ impl Cpu {
         }
     }
 
-    fn addc_m(&mut self, mem: &Mem) {
+    fn addc_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1324,7 +1440,7 @@ impl Cpu {
         }
     }
 
-    fn addc_d8(&mut self, mem: &Mem) {
+    fn addc_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1376,7 +1492,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn sub_m(&mut self, mem: &Mem) {
+    fn sub_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1394,7 +1510,7 @@ impl Cpu {
         }
     }
 
-    fn sub_d8(&mut self, mem: &Mem) {
+    fn sub_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1412,7 +1528,7 @@ impl Cpu {
         }
     }
 
-    fn subc_m(&mut self, mem: &Mem) {
+    fn subc_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1434,7 +1550,7 @@ impl Cpu {
         }
     }
 
-    fn subc_d8(&mut self, mem: &Mem) {
+    fn subc_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1469,7 +1585,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn and_m(&mut self, mem: &Mem) {
+    fn and_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1487,7 +1603,7 @@ impl Cpu {
         }
     }
 
-    fn and_d8(&mut self, mem: &Mem) {
+    fn and_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1518,7 +1634,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn xor_m(&mut self, mem: &Mem) {
+    fn xor_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1536,7 +1652,7 @@ impl Cpu {
         }
     }
 
-    fn xor_d8(&mut self, mem: &Mem) {
+    fn xor_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1567,7 +1683,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn or_m(&mut self, mem: &Mem) {
+    fn or_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1585,7 +1701,7 @@ impl Cpu {
         }
     }
 
-    fn or_d8(&mut self, mem: &Mem) {
+    fn or_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1614,7 +1730,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn cp_m(&mut self, mem: &Mem) {
+    fn cp_m(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1630,7 +1746,7 @@ impl Cpu {
         }
     }
 
-    fn cp_d8(&mut self, mem: &Mem) {
+    fn cp_d8(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 let a = self.get_byte_reg(&HalfReg::A);
@@ -1648,7 +1764,7 @@ impl Cpu {
 
     // FUNCTION
 
-    fn call(&mut self, mem: &mut Mem) {
+    fn call(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -1656,15 +1772,14 @@ impl Cpu {
             3 => {
                 self.store[1] = self.get_op(mem);
             },
-            4 => {
-                self.SP -= 1;
-                mem.set(self.SP, (self.PC >> 8) as u8);
-            },
+            // an internal cycle comes before the pushes
             5 => {
-                self.SP -= 1;
-                mem.set(self.SP, self.PC as u8);
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, (self.PC >> 8) as u8);
             },
             6 => {
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, self.PC as u8);
                 self.PC = (self.store[1] as u16) << 8 | self.store[0] as u16;
                 self.reset();
             },
@@ -1672,7 +1787,7 @@ impl Cpu {
         }
     }
 
-    fn call_con(&mut self, mem: &mut Mem, flag: Flag, check: bool) {
+    fn call_con(&mut self, mem: &mut dyn Bus, flag: Flag, check: bool) {
         match self.current_cycle {
             2 => {
                 self.store[0] = self.get_op(mem);
@@ -1683,15 +1798,14 @@ impl Cpu {
                     self.reset();
                 }
             },
-            4 => {
-                self.SP -= 1;
-                mem.set(self.SP, (self.PC >> 8) as u8);
-            },
+            // an internal cycle comes before the pushes
             5 => {
-                self.SP -= 1;
-                mem.set(self.SP, self.PC as u8);
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, (self.PC >> 8) as u8);
             },
             6 => {
+                self.SP = self.SP.wrapping_sub(1);
+                mem.set(self.SP, self.PC as u8);
                 self.PC = (self.store[1] as u16) << 8 | self.store[0] as u16;
                 self.reset();
             }
@@ -1699,15 +1813,15 @@ impl Cpu {
         }
     }
 
-    fn ret(&mut self, mem: &Mem) {
+    fn ret(&mut self, mem: &dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             3 => {
                 self.store[1] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             4 => {
                 self.PC = (self.store[1] as u16) << 8 | self.store[0] as u16;
@@ -1717,15 +1831,15 @@ impl Cpu {
         }
     }
 
-    fn reti(&mut self, mem: &mut Mem) {
+    fn reti(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             3 => {
                 self.store[1] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             4 => {
                 self.ime = true;
@@ -1736,7 +1850,7 @@ impl Cpu {
         }
     }
 
-    fn ret_con(&mut self, mem: &Mem, flag: Flag, check: bool) {
+    fn ret_con(&mut self, mem: &dyn Bus, flag: Flag, check: bool) {
         match self.current_cycle {
             2 => {
                 if !(self.get_flag(flag) == check) {
@@ -1745,11 +1859,11 @@ impl Cpu {
             },
             3 => {
                 self.store[0] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             4 => {
                 self.store[1] = mem.get(self.SP);
-                self.SP += 1;
+                self.SP = self.SP.wrapping_add(1);
             },
             5 => {
                 self.PC = (self.store[1] as u16) << 8 | self.store[0] as u16;
@@ -1761,16 +1875,15 @@ impl Cpu {
 
     // INTERUPT
 
-    fn rst(&mut self, mem: &mut Mem, counter: u16) {
+    fn rst(&mut self, mem: &mut dyn Bus, counter: u16) {
         match self.current_cycle {
-            2 => {
-                mem.set(self.SP - 1, (self.PC >> 8) as u8);
-            },
+            // an internal cycle comes before the pushes
             3 => {
-                mem.set(self.SP - 2, self.PC as u8);
+                mem.set(self.SP.wrapping_sub(1), (self.PC >> 8) as u8);
             },
             4 => {
-                self.SP -= 2;
+                mem.set(self.SP.wrapping_sub(2), self.PC as u8);
+                self.SP = self.SP.wrapping_sub(2);
                 self.PC = 0x0000 | counter;
                 self.reset();
             },
@@ -1780,32 +1893,31 @@ impl Cpu {
 
     // STACK
 
-    fn push(&mut self, reg: &Reg, mem: &mut Mem) {
+    fn push(&mut self, reg: &Reg, mem: &mut dyn Bus) {
         match self.current_cycle {
-            2 => {
-                let v = self.get_word_reg(reg);
-                mem.set(self.SP - 1, (v >> 8) as u8)
-            },
+            // an internal cycle comes before the pushes
             3 => {
                 let v = self.get_word_reg(reg);
-                mem.set(self.SP - 2, v as u8)
+                mem.set(self.SP.wrapping_sub(1), (v >> 8) as u8)
             },
             4 => {
-                self.SP -= 2;
+                let v = self.get_word_reg(reg);
+                mem.set(self.SP.wrapping_sub(2), v as u8);
+                self.SP = self.SP.wrapping_sub(2);
                 self.reset();
             },
             _ => ()
         }
     }
 
-    fn pop(&mut self, reg: &Reg, mem: &mut Mem) {
+    fn pop(&mut self, reg: &Reg, mem: &mut dyn Bus) {
         match self.current_cycle {
             2 => {
                 self.store[0] = mem.get(self.SP);
             },
             3 => {
-                self.store[1] = mem.get(self.SP + 1);
-                self.SP += 2;
+                self.store[1] = mem.get(self.SP.wrapping_add(1));
+                self.SP = self.SP.wrapping_add(2);
 
                 match reg {
                     &Reg::AF => self.set_word_reg(reg, (self.store[1] as u16) << 8 | (self.store[0] & 0xf0) as u16),
@@ -1819,9 +1931,11 @@ impl Cpu {
 
     // EXTENDED
 
-    fn extended(&mut self, mem: &mut Mem) {
+    fn extended(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
-            1 => {
+            // the second opcode byte is its own fetch
+            1 => (),
+            2 => {
                 self.store[0] = self.get_op(mem);
                 self.execute_extended(mem);
             },
@@ -1829,7 +1943,7 @@ impl Cpu {
         }
     }
 
-    fn execute_extended(&mut self, mem: &mut Mem) {
+    fn execute_extended(&mut self, mem: &mut dyn Bus) {
         match self.store[0] {
             0x00 => self.rlc(&HalfReg::B),
             0x01 => self.rlc(&HalfReg::C),
@@ -2119,7 +2233,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn rlc_m(&mut self, mem: &mut Mem) {
+    fn rlc_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2155,7 +2269,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn rrc_m(&mut self, mem: &mut Mem) {
+    fn rrc_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2192,7 +2306,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn rl_m(&mut self, mem: &mut Mem) {
+    fn rl_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2230,7 +2344,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn rr_m(&mut self, mem: &mut Mem) {
+    fn rr_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2267,7 +2381,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn sla_m(&mut self, mem: &mut Mem) {
+    fn sla_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2304,7 +2418,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn sra_m(&mut self, mem: &mut Mem) {
+    fn sra_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2341,7 +2455,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn swap_m(&mut self, mem: &mut Mem) {
+    fn swap_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2377,7 +2491,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn srl_m(&mut self, mem: &mut Mem) {
+    fn srl_m(&mut self, mem: &mut dyn Bus) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2414,13 +2528,11 @@ impl Cpu {
         self.reset();
     }
 
-    fn bit_m(&mut self, mem: &mut Mem, bit_num: u8) {
+    // only reads (HL), so one cycle shorter than the other (HL) ops
+    fn bit_m(&mut self, mem: &mut dyn Bus, bit_num: u8) {
         match self.current_cycle {
             3 => {
-                self.store[1] = mem.get(self.HL);
-            },
-            4 => {
-                let value = self.store[1];
+                let value = mem.get(self.HL);
                 let bit = (value >> bit_num) & 1 == 1;
 
                 self.set_flag(Flag::Z, !bit);
@@ -2441,7 +2553,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn res_m(&mut self, mem: &mut Mem, bit_mask: u8) {
+    fn res_m(&mut self, mem: &mut dyn Bus, bit_mask: u8) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -2465,7 +2577,7 @@ impl Cpu {
         self.reset();
     }
 
-    fn set_m(&mut self, mem: &mut Mem, bit_mask: u8) {
+    fn set_m(&mut self, mem: &mut dyn Bus, bit_mask: u8) {
         match self.current_cycle {
             3 => {
                 self.store[1] = mem.get(self.HL);
@@ -0,0 +1,236 @@
+use wasm_bindgen::prelude::*;
+
+// operand placeholders in the templates, d8/d16 are immediates, a8/a16 addresses,
+// r8 a relative jump and e8 a signed offset added to SP
+const BASE: [&str; 256] = [
+    "NOP", "LD BC,d16", "LD (BC),A", "INC BC",
+    "INC B", "DEC B", "LD B,d8", "RLCA",
+    "LD (a16),SP", "ADD HL,BC", "LD A,(BC)", "DEC BC",
+    "INC C", "DEC C", "LD C,d8", "RRCA",
+
+    "STOP", "LD DE,d16", "LD (DE),A", "INC DE",
+    "INC D", "DEC D", "LD D,d8", "RLA",
+    "JR r8", "ADD HL,DE", "LD A,(DE)", "DEC DE",
+    "INC E", "DEC E", "LD E,d8", "RRA",
+
+    "JR NZ,r8", "LD HL,d16", "LD (HL+),A", "INC HL",
+    "INC H", "DEC H", "LD H,d8", "DAA",
+    "JR Z,r8", "ADD HL,HL", "LD A,(HL+)", "DEC HL",
+    "INC L", "DEC L", "LD L,d8", "CPL",
+
+    "JR NC,r8", "LD SP,d16", "LD (HL-),A", "INC SP",
+    "INC (HL)", "DEC (HL)", "LD (HL),d8", "SCF",
+    "JR C,r8", "ADD HL,SP", "LD A,(HL-)", "DEC SP",
+    "INC A", "DEC A", "LD A,d8", "CCF",
+
+    "LD B,B", "LD B,C", "LD B,D", "LD B,E",
+    "LD B,H", "LD B,L", "LD B,(HL)", "LD B,A",
+    "LD C,B", "LD C,C", "LD C,D", "LD C,E",
+    "LD C,H", "LD C,L", "LD C,(HL)", "LD C,A",
+
+    "LD D,B", "LD D,C", "LD D,D", "LD D,E",
+    "LD D,H", "LD D,L", "LD D,(HL)", "LD D,A",
+    "LD E,B", "LD E,C", "LD E,D", "LD E,E",
+    "LD E,H", "LD E,L", "LD E,(HL)", "LD E,A",
+
+    "LD H,B", "LD H,C", "LD H,D", "LD H,E",
+    "LD H,H", "LD H,L", "LD H,(HL)", "LD H,A",
+    "LD L,B", "LD L,C", "LD L,D", "LD L,E",
+    "LD L,H", "LD L,L", "LD L,(HL)", "LD L,A",
+
+    "LD (HL),B", "LD (HL),C", "LD (HL),D", "LD (HL),E",
+    "LD (HL),H", "LD (HL),L", "HALT", "LD (HL),A",
+    "LD A,B", "LD A,C", "LD A,D", "LD A,E",
+    "LD A,H", "LD A,L", "LD A,(HL)", "LD A,A",
+
+    "ADD A,B", "ADD A,C", "ADD A,D", "ADD A,E",
+    "ADD A,H", "ADD A,L", "ADD A,(HL)", "ADD A,A",
+    "ADC A,B", "ADC A,C", "ADC A,D", "ADC A,E",
+    "ADC A,H", "ADC A,L", "ADC A,(HL)", "ADC A,A",
+
+    "SUB B", "SUB C", "SUB D", "SUB E",
+    "SUB H", "SUB L", "SUB (HL)", "SUB A",
+    "SBC A,B", "SBC A,C", "SBC A,D", "SBC A,E",
+    "SBC A,H", "SBC A,L", "SBC A,(HL)", "SBC A,A",
+
+    "AND B", "AND C", "AND D", "AND E",
+    "AND H", "AND L", "AND (HL)", "AND A",
+    "XOR B", "XOR C", "XOR D", "XOR E",
+    "XOR H", "XOR L", "XOR (HL)", "XOR A",
+
+    "OR B", "OR C", "OR D", "OR E",
+    "OR H", "OR L", "OR (HL)", "OR A",
+    "CP B", "CP C", "CP D", "CP E",
+    "CP H", "CP L", "CP (HL)", "CP A",
+
+    "RET NZ", "POP BC", "JP NZ,a16", "JP a16",
+    "CALL NZ,a16", "PUSH BC", "ADD A,d8", "RST $00",
+    "RET Z", "RET", "JP Z,a16", "PREFIX CB",
+    "CALL Z,a16", "CALL a16", "ADC A,d8", "RST $08",
+
+    "RET NC", "POP DE", "JP NC,a16", "DB $D3",
+    "CALL NC,a16", "PUSH DE", "SUB d8", "RST $10",
+    "RET C", "RETI", "JP C,a16", "DB $DB",
+    "CALL C,a16", "DB $DD", "SBC A,d8", "RST $18",
+
+    "LDH (a8),A", "POP HL", "LD ($FF00+C),A", "DB $E3",
+    "DB $E4", "PUSH HL", "AND d8", "RST $20",
+    "ADD SP,e8", "JP HL", "LD (a16),A", "DB $EB",
+    "DB $EC", "DB $ED", "XOR d8", "RST $28",
+
+    "LDH A,(a8)", "POP AF", "LD A,($FF00+C)", "DI",
+    "DB $F4", "PUSH AF", "OR d8", "RST $30",
+    "LD HL,SP+e8", "LD SP,HL", "LD A,(a16)", "EI",
+    "DB $FC", "DB $FD", "CP d8", "RST $38",
+];
+
+// M-cycles, conditional branches give the cost when the branch is not taken,
+// 0xCB only covers the prefix byte and the undefined opcodes never finish
+const CYCLES: [u8; 256] = [
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1,
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 1, 3, 6, 2, 4,
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4,
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4,
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4,
+];
+
+const CB_OPS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const CB_BITS: [&str; 3] = ["BIT", "RES", "SET"];
+const REGS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub addr: u16,
+    // ROM bank the instruction was read from, None outside cartridge ROM
+    pub bank: Option<u16>,
+    bytes: Vec<u8>,
+    mnemonic: String,
+    pub length: u16,
+    // a conditional branch costs `cycles` when not taken and `branch_cycles` when taken
+    pub cycles: u8,
+    pub branch_cycles: u8,
+}
+
+#[wasm_bindgen]
+impl Instruction {
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mnemonic(&self) -> String {
+        self.mnemonic.clone()
+    }
+
+    // 01:4000 for banked ROM, a plain address everywhere else
+    #[wasm_bindgen(getter)]
+    pub fn location(&self) -> String {
+        match self.bank {
+            Some(bank) => format!("{:02X}:{:04X}", bank, self.addr),
+            None => format!("{:04X}", self.addr),
+        }
+    }
+}
+
+// decodes the instruction whose opcode is the first of `bytes`
+pub fn decode(addr: u16, bank: Option<u16>, bytes: &[u8]) -> Instruction {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let opcode = byte(0);
+    let length = length(opcode);
+    let (cycles, branch_cycles) = cycles(opcode, byte(1));
+    Instruction {
+        addr,
+        bank,
+        bytes: (0..length as usize).map(byte).collect(),
+        mnemonic: mnemonic(addr, bytes),
+        length,
+        cycles,
+        branch_cycles,
+    }
+}
+
+// instruction length in bytes, including the opcode
+pub fn length(opcode: u8) -> u16 {
+    match opcode {
+        // STOP skips the byte after it
+        0x10 | 0xCB => 2,
+        _ => {
+            let template = BASE[opcode as usize];
+            if ["d16", "a16"].iter().any(|operand| template.contains(operand)) {
+                3
+            } else if ["d8", "a8", "r8", "e8"].iter().any(|operand| template.contains(operand)) {
+                2
+            } else {
+                1
+            }
+        }
+    }
+}
+
+// not taken and taken, `cb` is only looked at behind the 0xCB prefix
+pub fn cycles(opcode: u8, cb: u8) -> (u8, u8) {
+    let cycles = CYCLES[opcode as usize];
+    match opcode {
+        0xCB if cb & 7 != 6 => (2, 2),
+        // BIT only reads (HL), the others write it back
+        0xCB if cb >> 6 == 1 => (3, 3),
+        0xCB => (4, 4),
+        0x20 | 0x28 | 0x30 | 0x38 => (cycles, 3),
+        0xC2 | 0xCA | 0xD2 | 0xDA => (cycles, 4),
+        0xC4 | 0xCC | 0xD4 | 0xDC => (cycles, 6),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => (cycles, 5),
+        _ => (cycles, cycles),
+    }
+}
+
+// `bytes` starts with the opcode at `addr`, missing operand bytes read as 0
+pub fn mnemonic(addr: u16, bytes: &[u8]) -> String {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let opcode = byte(0);
+    if opcode == 0xCB {
+        return cb_mnemonic(byte(1));
+    }
+
+    let template = BASE[opcode as usize];
+    let d16 = (byte(2) as u16) << 8 | byte(1) as u16;
+    let offset = byte(1) as i8;
+    if template.contains("d16") {
+        template.replace("d16", &format!("${:04X}", d16))
+    } else if template.contains("a16") {
+        template.replace("a16", &format!("${:04X}", d16))
+    } else if template.contains("d8") {
+        template.replace("d8", &format!("${:02X}", byte(1)))
+    } else if template.contains("a8") {
+        template.replace("a8", &format!("$FF{:02X}", byte(1)))
+    } else if template.contains("r8") {
+        let target = addr.wrapping_add(2).wrapping_add(offset as u16);
+        template.replace("r8", &format!("${:04X}", target))
+    } else if template.contains("+e8") {
+        template.replace("+e8", &format!("{:+}", offset))
+    } else if template.contains("e8") {
+        template.replace("e8", &format!("{}", offset))
+    } else {
+        template.to_string()
+    }
+}
+
+fn cb_mnemonic(op: u8) -> String {
+    let reg = REGS[(op & 7) as usize];
+    match op >> 6 {
+        0 => format!("{} {}", CB_OPS[(op >> 3) as usize], reg),
+        group => format!("{} {},{}", CB_BITS[group as usize - 1], op >> 3 & 7, reg),
+    }
+}
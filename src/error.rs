@@ -0,0 +1,52 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, PartialEq)]
+pub enum EmuError {
+    UnreadableRom { path: String, reason: String },
+    UnsupportedMapper(u8),
+    TruncatedRom { expected: usize, actual: usize },
+    BadRamSize(u8),
+    ChecksumMismatch { expected: u8, actual: u8 },
+    UnsupportedStateVersion(u16),
+    StateCartMismatch,
+    CorruptState,
+    BadGbsHeader,
+    NoSuchTrack(u8),
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::UnreadableRom { path, reason } =>
+                write!(f, "Could not read {}: {}", path, reason),
+            EmuError::UnsupportedMapper(cart_type) =>
+                write!(f, "Unsupported cartridge type {:#04X}", cart_type),
+            EmuError::TruncatedRom { expected, actual } =>
+                write!(f, "ROM is truncated, expected {} bytes but got {}", expected, actual),
+            EmuError::BadRamSize(header) =>
+                write!(f, "Invalid RAM size {:#04X} in header", header),
+            EmuError::ChecksumMismatch { expected, actual } =>
+                write!(f, "Header checksum is {:#04X} but the header sums to {:#04X}", expected, actual),
+            EmuError::UnsupportedStateVersion(version) =>
+                write!(f, "Save state version {} is not supported", version),
+            EmuError::StateCartMismatch =>
+                write!(f, "Save state was made with a different cartridge"),
+            EmuError::CorruptState =>
+                write!(f, "Save state is corrupt"),
+            EmuError::BadGbsHeader =>
+                write!(f, "Not a supported GBS file"),
+            EmuError::NoSuchTrack(track) =>
+                write!(f, "Track {} does not exist", track),
+        }
+    }
+}
+
+impl std::error::Error for EmuError {}
+
+// thrown as a regular Error on the JS side
+impl From<EmuError> for JsValue {
+    fn from(error: EmuError) -> Self {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
@@ -0,0 +1,174 @@
+use wasm_bindgen::prelude::*;
+
+use crate::cartridge::{CartridgeInfo, HEADER_END};
+use crate::error::EmuError;
+
+pub const GBS_HEADER_SIZE: usize = 0x70;
+const GBS_MAGIC: &[u8; 3] = b"GBS";
+const GBS_VERSION: u8 = 1;
+
+// the player returns here from INIT and PLAY, the code is JR -2
+pub const IDLE_ADDR: u16 = 0x0100;
+const IDLE_LOOP: [u8; 2] = [0x18, 0xFE];
+// the image is given an MBC5 header with 8KB of RAM so banking and 0xA000 work
+const IMAGE_TYPE: u8 = 0x1A;
+const IMAGE_RAM_SIZE: u8 = 0x02;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const MAX_BANKS: usize = 256;
+const M_CYCLES_PER_SECOND: u32 = 1_048_576;
+// a vblank driven track is played once per frame
+const VBLANK_PERIOD: u32 = 17556;
+// TAC input clocks in Hz
+const TIMER_CLOCKS: [u32; 4] = [4096, 262144, 65536, 16384];
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct GbsInfo {
+    title: String,
+    author: String,
+    copyright: String,
+    pub track_count: u8,
+    // zero based, the file stores it one based
+    pub first_track: u8,
+    pub load_addr: u16,
+    pub init_addr: u16,
+    pub play_addr: u16,
+    pub stack_pointer: u16,
+    pub timer_modulo: u8,
+    pub timer_control: u8,
+}
+
+impl GbsInfo {
+    pub fn from_file(data: &[u8]) -> Result<Self, EmuError> {
+        if data.len() < GBS_HEADER_SIZE {
+            return Err(EmuError::TruncatedRom { expected: GBS_HEADER_SIZE, actual: data.len() });
+        }
+        if &data[0..3] != GBS_MAGIC || data[3] != GBS_VERSION {
+            return Err(EmuError::BadGbsHeader);
+        }
+
+        let word = |addr: usize| data[addr] as u16 | (data[addr + 1] as u16) << 8;
+        let info = Self {
+            title: GbsInfo::text(&data[0x10..0x30]),
+            author: GbsInfo::text(&data[0x30..0x50]),
+            copyright: GbsInfo::text(&data[0x50..0x70]),
+            track_count: data[0x04],
+            first_track: data[0x05].saturating_sub(1),
+            load_addr: word(0x06),
+            init_addr: word(0x08),
+            play_addr: word(0x0A),
+            stack_pointer: word(0x0C),
+            timer_modulo: data[0x0E],
+            timer_control: data[0x0F],
+        };
+
+        // the code goes after the cartridge header and has to fit in 256 banks
+        let end = info.load_addr as usize + data.len() - GBS_HEADER_SIZE;
+        if info.track_count == 0 || (info.load_addr as usize) < HEADER_END || end > ROM_BANK_SIZE * MAX_BANKS {
+            return Err(EmuError::BadGbsHeader);
+        }
+        Ok(info)
+    }
+
+    fn text(bytes: &[u8]) -> String {
+        bytes.iter()
+            .take_while(|c| **c != 0)
+            .map(|c| if c.is_ascii_graphic() || *c == b' ' { *c as char } else { '?' })
+            .collect()
+    }
+
+    pub fn timer_driven(&self) -> bool {
+        self.timer_control & 4 != 0
+    }
+
+    // M-cycles between two PLAY calls
+    pub fn play_period(&self, tma: u8, tac: u8) -> u32 {
+        if !self.timer_driven() {
+            return VBLANK_PERIOD;
+        }
+        let ticks = 256 - tma as u32;
+        M_CYCLES_PER_SECOND / TIMER_CLOCKS[(tac & 3) as usize] * ticks
+    }
+
+    // The code is placed at its load address in a ROM image that the regular
+    // cartridge path can load. The RST vectors jump to load address + vector
+    // as the format expects.
+    pub fn build_image(&self, data: &[u8]) -> Vec<u8> {
+        let code = &data[GBS_HEADER_SIZE..];
+        let end = self.load_addr as usize + code.len();
+        let banks = end.div_ceil(ROM_BANK_SIZE).next_power_of_two().max(2);
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        rom[self.load_addr as usize..end].copy_from_slice(code);
+
+        for vector in (0..0x40).step_by(8) {
+            let target = self.load_addr + vector as u16;
+            rom[vector..vector + 3].copy_from_slice(&[0xC3, target as u8, (target >> 8) as u8]);
+        }
+        rom[IDLE_ADDR as usize..IDLE_ADDR as usize + 2].copy_from_slice(&IDLE_LOOP);
+        rom[0x0134..0x0137].copy_from_slice(GBS_MAGIC);
+        rom[0x0147] = IMAGE_TYPE;
+        rom[0x0148] = banks.trailing_zeros() as u8 - 1;
+        rom[0x0149] = IMAGE_RAM_SIZE;
+        rom[0x014D] = CartridgeInfo::compute_header_checksum(&rom);
+        rom
+    }
+}
+
+#[wasm_bindgen]
+impl GbsInfo {
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn author(&self) -> String {
+        self.author.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn copyright(&self) -> String {
+        self.copyright.clone()
+    }
+}
+
+// schedules the PLAY calls, a call that comes due while the previous one is
+// still running waits for it to return
+pub struct GbsPlayer {
+    pub info: GbsInfo,
+    pub track: u8,
+    until_play: u32,
+    pending: bool,
+}
+
+impl GbsPlayer {
+    pub fn new(info: GbsInfo) -> Self {
+        Self {
+            track: info.first_track,
+            info,
+            until_play: VBLANK_PERIOD,
+            pending: false,
+        }
+    }
+
+    pub fn restart(&mut self, track: u8, tma: u8, tac: u8) {
+        self.track = track;
+        self.until_play = self.info.play_period(tma, tac);
+        self.pending = false;
+    }
+
+    // one M-cycle, true when PLAY should be called now
+    pub fn tick(&mut self, idle: bool, tma: u8, tac: u8) -> bool {
+        self.until_play -= 1;
+        if self.until_play == 0 {
+            self.until_play = self.info.play_period(tma, tac);
+            self.pending = true;
+        }
+        if self.pending && idle {
+            self.pending = false;
+            return true;
+        }
+        false
+    }
+}
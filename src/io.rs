@@ -4,6 +4,9 @@ pub const SC_ADDR: u16 = 0xFF02;
 pub const P1_ADDR: u16 = 0xFF00;
 use wasm_bindgen::prelude::*;
 
+use crate::error::EmuError;
+use crate::state::{Savable, StateReader, StateWriter};
+
 #[wasm_bindgen]
 pub struct Serial {
     SB: u8,
@@ -70,6 +73,32 @@ impl Serial {
     }
 }
 
+impl Savable for Serial {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.SB);
+        state.u8(self.SC);
+        for c in self.buffer.iter() {
+            state.u32(*c as u32);
+        }
+        state.u32(self.buffer_pos as u32);
+        state.vec(self.string_buffer.as_bytes());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.SB = state.u8()?;
+        self.SC = state.u8()?;
+        for c in self.buffer.iter_mut() {
+            *c = char::from_u32(state.u32()?).ok_or(EmuError::CorruptState)?;
+        }
+        self.buffer_pos = state.u32()? as usize;
+        if self.buffer_pos >= self.buffer.len() {
+            return Err(EmuError::CorruptState);
+        }
+        self.string_buffer = String::from_utf8(state.vec()?).map_err(|_| EmuError::CorruptState)?;
+        Ok(())
+    }
+}
+
 pub const DIV_ADDR: u16 = 0xFF04;
 pub const TIMA_ADDR: u16 = 0xFF05;
 pub const TMA_ADDR: u16 = 0xFF06;
@@ -83,7 +112,10 @@ pub struct Timer {
     pub TIMA: u8,
     pub TMA: u8,
     pub TAC: u8,
-    overflowed: bool,
+    // TIMA overflowed last cycle, it reads 0 until the reload next cycle
+    reload_pending: bool,
+    // TIMA was loaded from TMA this cycle, writes to TIMA are ignored
+    reloaded: bool,
     last_edge: u16
 }
 
@@ -97,18 +129,37 @@ impl Timer {
             TIMA: 0,
             TMA: 0,
             TAC: 0,
-            overflowed: false,
+            reload_pending: false,
+            reloaded: false,
             last_edge: 0,
         }
     }
 
     pub fn write(&mut self, addr: u16, value: u8) {
         match addr {
-            DIV_ADDR => self.DIV = 0,
-            TIMA_ADDR => self.TIMA = value,
-            TMA_ADDR => self.TMA = value,
+            // the selected bit drops to 0 with the rest of the counter and can clock TIMA
+            DIV_ADDR => {
+                self.DIV = 0;
+                self.check_edge();
+            },
+            TIMA_ADDR => {
+                if self.reload_pending {
+                    // writing in the cycle before the reload cancels it and the interrupt
+                    self.reload_pending = false;
+                    self.TIMA = value;
+                } else if !self.reloaded {
+                    self.TIMA = value;
+                }
+            },
+            TMA_ADDR => {
+                self.TMA = value;
+                if self.reloaded {
+                    self.TIMA = value;
+                }
+            },
             TAC_ADDR => {
                 self.TAC = value;
+                self.check_edge();
             },
             _ => panic!("Timer does not exist at this address")
         }
@@ -128,27 +179,34 @@ impl Timer {
         self.DIV = self.DIV.wrapping_add(CYCLE as u16);
     }
 
-    fn tick_tima(&mut self) {
+    // TIMA counts on the falling edge of the selected DIV bit ANDed with the enable bit
+    fn check_edge(&mut self) {
         let significant_bit = self.control();
         let enabled_bit = self.timer_enabled();
         let current_edge = (self.DIV >> significant_bit) & enabled_bit;
         if self.last_edge == 1 && current_edge == 0 {
             let (result, overflow) = self.TIMA.overflowing_add(1);
+            self.TIMA = result;
             if overflow {
-                self.TIMA = self.TMA;
-            } else {
-                self.TIMA = result;
+                self.reload_pending = true;
             }
-            self.overflowed = overflow;
         }
         self.last_edge = current_edge;
     }
 
+    // returns true on the cycle TIMA is reloaded and the interrupt is requested
     pub fn tick(&mut self) -> bool {
-        self.overflowed = false;
+        self.reloaded = false;
+        let mut interrupt = false;
+        if self.reload_pending {
+            self.reload_pending = false;
+            self.reloaded = true;
+            self.TIMA = self.TMA;
+            interrupt = true;
+        }
         self.tick_div();
-        self.tick_tima();
-        self.overflowed
+        self.check_edge();
+        interrupt
     }
 
     fn timer_enabled(&self) -> u16 {
@@ -171,12 +229,36 @@ impl Timer {
             TIMA: self.TIMA,
             TMA: self.TMA,
             TAC: self.TAC,
-            overflowed: false,
-            last_edge: 0,
+            reload_pending: self.reload_pending,
+            reloaded: self.reloaded,
+            last_edge: self.last_edge,
         }
     }
 }
 
+impl Savable for Timer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.DIV);
+        state.u8(self.TIMA);
+        state.u8(self.TMA);
+        state.u8(self.TAC);
+        state.bool(self.reload_pending);
+        state.bool(self.reloaded);
+        state.u16(self.last_edge);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.DIV = state.u16()?;
+        self.TIMA = state.u8()?;
+        self.TMA = state.u8()?;
+        self.TAC = state.u8()?;
+        self.reload_pending = state.bool()?;
+        self.reloaded = state.bool()?;
+        self.last_edge = state.u16()?;
+        Ok(())
+    }
+}
+
 #[wasm_bindgen]
 pub enum Button {
     A,
@@ -255,8 +337,9 @@ impl Joypad {
             0xC0 | ((!self.down) as u8) << 3 | ((!self.up) as u8) << 2 | ((!self.left) as u8) << 1 | (!self.right) as u8
         } else if self.action_select {
             0xC0 | ((!self.start) as u8) << 3 | ((!self.select) as u8) << 2 | ((!self.b) as u8) << 1 | ((!self.a) as u8)
-        } else { 
-            0xC0 
+        } else {
+            // nothing selected, every line stays high
+            0xCF
         }
     }
 
@@ -285,4 +368,29 @@ impl Joypad {
     pub fn get_arrow(&self) -> u8 {
         0xC0 | ((!self.down) as u8) << 3 | ((!self.up) as u8) << 2 | ((!self.left) as u8) << 1 | (!self.right) as u8
     }
-}
\ No newline at end of file
+}
+
+impl Savable for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        let buttons = [self.a, self.b, self.start, self.select, self.up, self.down, self.left, self.right];
+        for pressed in buttons {
+            state.bool(pressed);
+        }
+        state.bool(self.arrow_select);
+        state.bool(self.action_select);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.a = state.bool()?;
+        self.b = state.bool()?;
+        self.start = state.bool()?;
+        self.select = state.bool()?;
+        self.up = state.bool()?;
+        self.down = state.bool()?;
+        self.left = state.bool()?;
+        self.right = state.bool()?;
+        self.arrow_select = state.bool()?;
+        self.action_select = state.bool()?;
+        Ok(())
+    }
+}
@@ -1,21 +1,47 @@
 
+pub mod bus;
 pub mod mem;
 pub mod cpu;
 pub mod ppu;
 pub mod io;
 pub mod mbc;
 pub mod apu;
-
-use std::{fs};
+pub mod mixer;
+pub mod cartridge;
+pub mod error;
+pub mod state;
+pub mod rewind;
+pub mod audio_sink;
+pub mod recorder;
+pub mod gbs;
+pub mod disasm;
+pub mod step;
+pub mod trace;
+
+use std::{fs, io::Write, path::Path};
 use wasm_bindgen::prelude::*;
 
-use cpu::{Cpu, DebugCpu};
+use apu::ChannelStatus;
+use cartridge::CartridgeInfo;
+use error::EmuError;
+use gbs::{GbsInfo, GbsPlayer, IDLE_ADDR};
+use cpu::{Cpu, CpuFault, DebugCpu};
+use disasm::Instruction;
 use io::{Button, Timer};
 use mem::{Mem};
+use recorder::Recording;
 use ppu::{Ppu};
+use rewind::Rewind;
+use step::StepRecord;
+use trace::Tracer;
+use state::{Savable, StateReader, StateWriter, STATE_MAGIC, STATE_VERSION};
+
+// M-cycles in one frame, GBS playback has no PPU to say when a frame is done
+const GBS_FRAME_CYCLES: u32 = 17556;
 
 enum CycleState {
     Break,
+    Fault,
     Ran,
 }
 
@@ -24,7 +50,13 @@ pub struct Emu {
     cpu: Cpu,
     mem: Mem,
     ppu: Ppu,
-    breakpoints: Vec<u16>
+    breakpoints: Vec<u16>,
+    cartridge: Option<CartridgeInfo>,
+    rewind: Option<Rewind>,
+    gbs: Option<GbsPlayer>,
+    // log the CPU's bus accesses for step_instruction
+    recording_step: bool,
+    tracer: Option<Tracer>,
 }
 
 #[wasm_bindgen]
@@ -34,11 +66,21 @@ impl Emu {
             cpu: Cpu::new(),
             mem: Mem::new(),
             ppu: Ppu::new(),
-            breakpoints: vec![]
+            breakpoints: vec![],
+            cartridge: None,
+            rewind: None,
+            gbs: None,
+            recording_step: false,
+            tracer: None,
         }
     }
 
     pub fn init(&mut self) {
+        // a GBS rip starts over with the current track instead
+        if let Some(track) = self.current_track() {
+            let _ = self.select_track(track);
+            return;
+        }
         self.cpu.PC = 0x100;
         self.cpu.SP = 0xFFFE;
         self.cpu.AF = 0x1180;
@@ -51,14 +93,72 @@ impl Emu {
         }
     }
 
+    // runs exactly one instruction or interrupt dispatch, a halted or stopped CPU only waits one cycle
+    pub fn step_instruction(&mut self) -> StepRecord {
+        let before = self.cpu.get_state();
+        let pc = self.cpu.PC;
+        let waiting = if self.cpu.fault().is_some() {
+            Some("LOCKED")
+        } else if self.cpu.is_stop {
+            Some("STOP")
+        } else if self.cpu.is_halt {
+            Some("HALT")
+        } else {
+            None
+        };
+        let bytes: Vec<u8> = match waiting {
+            Some(_) => vec![],
+            None => (0..disasm::length(self.mem.peek(pc)))
+                .map(|i| self.mem.peek(pc.wrapping_add(i)))
+                .collect(),
+        };
+
+        let mut accesses = vec![];
+        let mut cycles = 0;
+        self.recording_step = true;
+        loop {
+            self.cycle(false);
+            cycles += 1;
+            for mut access in self.mem.take_accesses() {
+                access.cycle = cycles as u8;
+                accesses.push(access);
+            }
+            if self.cpu.get_cycle() == 1 {
+                break;
+            }
+        }
+        self.recording_step = false;
+
+        let interrupt = waiting.is_none() && self.cpu.dispatching;
+        let mnemonic = match waiting {
+            Some(state) => state.to_string(),
+            None if interrupt => format!("INT ${:02X}", self.cpu.PC),
+            None => disasm::mnemonic(pc, &bytes),
+        };
+        let bytes = if interrupt { vec![] } else { bytes };
+        StepRecord {
+            pc,
+            bytes,
+            mnemonic,
+            cycles,
+            interrupt,
+            before,
+            after: self.cpu.get_state(),
+            accesses,
+        }
+    }
+
     pub fn tick_till_frame_done(&mut self) -> bool {
+        if self.gbs.is_some() {
+            return self.play_gbs_frame();
+        }
         let result = self.cycle(true);
-        if let CycleState::Break = result {
+        if let CycleState::Break | CycleState::Fault = result {
             return false;
         }
         while !self.ppu.ready {
             let result = self.cycle(true);
-            if let CycleState::Break = result {
+            if let CycleState::Break | CycleState::Fault = result {
                 return false;
             }
         }
@@ -69,15 +169,91 @@ impl Emu {
         self.ppu.get_buffer().clone().to_vec()
     }
 
-    pub fn load_rom_data(&mut self, rom: Vec<u8>) {
-        self.mem.load_cart(rom);
+    pub fn load_rom_data(&mut self, rom: Vec<u8>) -> Result<CartridgeInfo, EmuError> {
+        let info = self.mem.load_cart(rom)?;
+        self.mem.lock_rom(true);
+        self.cartridge = Some(info.clone());
+        self.gbs = None;
+        // old snapshots belong to the previous cart
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        Ok(info)
+    }
+
+    // switches to playing a GBS rip, the PPU stays off and the first track starts
+    pub fn load_gbs(&mut self, data: Vec<u8>) -> Result<GbsInfo, EmuError> {
+        let info = GbsInfo::from_file(&data)?;
+        self.mem.load_cart(info.build_image(&data))?;
         self.mem.lock_rom(true);
+        self.cartridge = None;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        let first = info.first_track;
+        self.gbs = Some(GbsPlayer::new(info.clone()));
+        self.select_track(first)?;
+        Ok(info)
+    }
+
+    pub fn get_gbs_info(&self) -> Option<GbsInfo> {
+        self.gbs.as_ref().map(|gbs| gbs.info.clone())
+    }
+
+    pub fn current_track(&self) -> Option<u8> {
+        self.gbs.as_ref().map(|gbs| gbs.track)
+    }
+
+    // tracks are zero based, resets the sound hardware and RAM then runs INIT
+    pub fn select_track(&mut self, track: u8) -> Result<(), EmuError> {
+        let info = match &self.gbs {
+            Some(gbs) if track < gbs.info.track_count => gbs.info.clone(),
+            _ => return Err(EmuError::NoSuchTrack(track)),
+        };
+
+        for addr in (0xC000..0xE000).chain(0xFF80..0xFFFF) {
+            self.mem.set(addr, 0);
+        }
+        // bank 1 in the switchable area, RAM enabled and cleared as well
+        self.mem.set(0x2000, 1);
+        self.mem.set(0x0000, 0x0A);
+        for addr in 0xA000..0xC000 {
+            self.mem.set(addr, 0);
+        }
+        // power cycling the APU clears its registers
+        self.mem.set(0xFF26, 0x00);
+        self.mem.set(0xFF26, 0x80);
+        self.mem.set(0xFF24, 0x77);
+        self.mem.set(0xFF25, 0xFF);
+        // the player calls INIT and PLAY itself, no interrupt may get in the way
+        self.mem.set(0xFFFF, 0);
+        self.mem.set(0xFF0F, 0);
+        self.mem.set(0xFF40, 0);
+        self.mem.set(0xFF06, info.timer_modulo);
+        self.mem.set(0xFF07, info.timer_control);
+
+        self.cpu = Cpu::new();
+        self.cpu.AF = (track as u16) << 8;
+        self.call_gbs(info.init_addr, info.stack_pointer);
+        if let Some(gbs) = &mut self.gbs {
+            gbs.restart(track, info.timer_modulo, info.timer_control);
+        }
+        Ok(())
+    }
+
+    pub fn get_cartridge_info(&self) -> Option<CartridgeInfo> {
+        self.cartridge.clone()
     }
 
     pub fn get_cpu_state(&self) -> DebugCpu {
         self.cpu.get_state()
     }
 
+    // set once the CPU hit an undefined opcode, tick_till_frame_done stops returning frames
+    pub fn get_cpu_fault(&self) -> Option<CpuFault> {
+        self.cpu.fault()
+    }
+
     pub fn get_mem_state(&self) -> Vec<u8> {
         let mut clone: Vec<u8> = vec![0; 0xFFFF];
         for i in 0..0xFFFF{
@@ -86,6 +262,20 @@ impl Emu {
         clone
     }
 
+    // `count` instructions from `addr` on, ROM addresses are tagged with the mapped bank
+    pub fn disassemble(&self, addr: u16, count: usize) -> Vec<Instruction> {
+        let mut instructions = Vec::with_capacity(count);
+        let mut addr = addr;
+        for _ in 0..count {
+            let length = disasm::length(self.mem.peek(addr));
+            let bytes: Vec<u8> = (0..length).map(|i| self.mem.peek(addr.wrapping_add(i))).collect();
+            let instruction = disasm::decode(addr, self.mem.rom_bank(addr), &bytes);
+            addr = addr.wrapping_add(instruction.length);
+            instructions.push(instruction);
+        }
+        instructions
+    }
+
     pub fn get_timer_state(&self) -> Timer {
         self.mem.get_timer()
     }
@@ -130,8 +320,160 @@ impl Emu {
         self.breakpoints = breakpoints; 
     } 
 
-    pub fn get_audio_channel1(&self) -> Vec<u8> {
-        self.mem.get_audio_buffers()[0].to_vec()
+    pub fn use_host_clock(&mut self, enabled: bool) {
+        if let Some(rtc) = self.mem.get_rtc() {
+            rtc.use_host_clock(enabled);
+        }
+    }
+
+    pub fn sync_rtc(&mut self, unix_time: f64) {
+        if let Some(rtc) = self.mem.get_rtc() {
+            rtc.sync(unix_time);
+        }
+    }
+
+    pub fn is_rumbling(&self) -> bool {
+        self.mem.get_rumble()
+    }
+
+    // 1 for motor on, 0 for motor off
+    pub fn take_rumble_events(&mut self) -> Vec<u8> {
+        self.mem.take_rumble_events().iter().map(|on| *on as u8).collect()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.mem.has_battery()
+    }
+
+    // cartridge RAM as a .sav file, clears the dirty flag. RTC carts stamp
+    // the trailer with `unix_time` so other emulators know how long it sat
+    pub fn export_save(&mut self, unix_time: f64) -> Vec<u8> {
+        self.mem.export_ram(unix_time)
+    }
+
+    // load a .sav file after the rom and before running
+    pub fn import_save(&mut self, data: Vec<u8>) {
+        self.mem.import_ram(&data);
+    }
+
+    pub fn is_save_dirty(&self) -> bool {
+        self.mem.ram_dirty()
+    }
+
+    // captures the mix, and each channel on its own when `per_channel` is set
+    pub fn start_recording(&mut self, per_channel: bool) {
+        self.mem.start_recording(per_channel);
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.mem.stop_recording()
+    }
+
+    // runs `frames` frames while recording, stops early on a breakpoint
+    pub fn record_audio(&mut self, frames: u32, per_channel: bool) -> Recording {
+        self.start_recording(per_channel);
+        for _ in 0..frames {
+            if !self.tick_till_frame_done() {
+                break;
+            }
+        }
+        self.stop_recording().unwrap()
+    }
+
+    // channels are numbered 0 to 3, anything else is ignored
+    pub fn mute_channel(&mut self, channel: usize, muted: bool) {
+        self.mem.mute_channel(channel, muted);
+    }
+
+    pub fn solo_channel(&mut self, channel: usize, soloed: bool) {
+        self.mem.solo_channel(channel, soloed);
+    }
+
+    pub fn get_channel_status(&self, channel: usize) -> Option<ChannelStatus> {
+        self.mem.get_apu().channel_status(channel)
+    }
+
+    // the channel's last frame of output (0-15) at 44.1 kHz, oldest first
+    pub fn get_channel_scope(&self, channel: usize) -> Vec<u8> {
+        self.mem.get_apu().scope(channel)
+    }
+
+    // sample rate of drain_audio, resets anything not drained yet
+    pub fn set_audio_rate(&mut self, rate: u32) {
+        self.mem.set_audio_rate(rate);
+    }
+
+    // copies interleaved stereo samples into `out`, returns how many floats were written
+    pub fn drain_audio(&mut self, out: &mut [f32]) -> usize {
+        self.mem.drain_audio(out)
+    }
+
+    // snapshot of the whole machine, can be taken between any two ticks
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.bytes(&STATE_MAGIC);
+        state.u16(STATE_VERSION);
+        self.save_cart_id(&mut state);
+        self.cpu.save_state(&mut state);
+        self.mem.save_state(&mut state);
+        self.ppu.save_state(&mut state);
+        state.into_bytes()
+    }
+
+    // a state only loads on top of the cartridge it was saved with
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), EmuError> {
+        let backup = self.save_state();
+        let result = self.read_state(data);
+        if result.is_err() {
+            self.read_state(&backup)?;
+        }
+        result
+    }
+
+    // capture a snapshot every `interval` frames, dropping the oldest past `budget` bytes
+    pub fn enable_rewind(&mut self, interval: u32, budget: usize) {
+        self.rewind = Some(Rewind::new(interval, budget));
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    // call once per finished frame
+    pub fn record_frame(&mut self) {
+        let due = match &mut self.rewind {
+            Some(rewind) => rewind.frame_done(),
+            None => false,
+        };
+        if due {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
+    }
+
+    // returns how many frames were actually rewound
+    pub fn rewind(&mut self, frames: u32) -> Result<u32, EmuError> {
+        let snapshot = match &mut self.rewind {
+            Some(rewind) => rewind.rewind(frames),
+            None => None,
+        };
+        match snapshot {
+            Some((rewound, state)) => {
+                self.load_state(&state)?;
+                Ok(rewound)
+            },
+            None => Ok(0),
+        }
+    }
+
+    pub fn rewind_available(&self) -> u32 {
+        self.rewind.as_ref().map_or(0, |rewind| rewind.available())
+    }
+
+    pub fn rewind_memory(&self) -> usize {
+        self.rewind.as_ref().map_or(0, |rewind| rewind.used())
     }
 }
 
@@ -149,6 +491,16 @@ impl Emu {
         &mut self.ppu
     }
 
+    // logs the registers before every instruction in the gameboy-doctor format
+    pub fn set_tracer(&mut self, out: Box<dyn Write>) {
+        self.tracer = Some(Tracer::new(out));
+    }
+
+    // stops tracing and hands the writer back, flushed
+    pub fn clear_tracer(&mut self) -> Option<Box<dyn Write>> {
+        self.tracer.take().map(Tracer::into_inner)
+    }
+
     pub fn write_mem(&mut self, values: &[u8; 50]) {
         let mut i: u16 = 0;
         for b in values {
@@ -177,32 +529,127 @@ impl Emu {
         }
     }
 
-    pub fn load_rom<S: Into<String>>(&mut self, name: S) {
-        let path = format!("./resources/{}", &name.into());
-        let boot_rom = &fs::read(path).expect("File Not Found");
-        let mut i: u16 = 0;
-        for instruction in boot_rom {
-            self.mem.set(i, *instruction);
-            i += 1;
-        }
+    pub fn load_rom<P: AsRef<Path>>(&mut self, path: P) -> Result<CartridgeInfo, EmuError> {
+        let path = path.as_ref();
+        let rom = fs::read(path).map_err(|e| EmuError::UnreadableRom {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        self.load_rom_data(rom)
     }
 
     pub fn get_serial(&self) -> String {
         self.mem.get_serial().get_buffer().clone()
     }
 
+    fn save_cart_id(&self, state: &mut StateWriter) {
+        match &self.cartridge {
+            Some(cart) => {
+                state.bool(true);
+                state.u8(cart.cartridge_type);
+                state.u8(cart.header_checksum);
+                state.u16(cart.global_checksum);
+            },
+            None => state.bool(false),
+        }
+    }
+
+    fn read_state(&mut self, data: &[u8]) -> Result<(), EmuError> {
+        let mut state = StateReader::new(data);
+        let mut magic = [0; 4];
+        state.bytes(&mut magic)?;
+        if magic != STATE_MAGIC {
+            return Err(EmuError::CorruptState);
+        }
+        let version = state.u16()?;
+        if version != STATE_VERSION {
+            return Err(EmuError::UnsupportedStateVersion(version));
+        }
+
+        let mut cart_id = StateWriter::new();
+        self.save_cart_id(&mut cart_id);
+        let cart_id = cart_id.into_bytes();
+        let mut saved_id = vec![0; cart_id.len()];
+        state.bytes(&mut saved_id)?;
+        if saved_id != cart_id {
+            return Err(EmuError::StateCartMismatch);
+        }
+
+        self.cpu.load_state(&mut state)?;
+        self.mem.load_state(&mut state)?;
+        self.ppu.load_state(&mut state)?;
+        if !state.finished() {
+            return Err(EmuError::CorruptState);
+        }
+        Ok(())
+    }
+
+    // pushes the idle loop as the return address and jumps to `addr`
+    fn call_gbs(&mut self, addr: u16, stack_pointer: u16) {
+        self.cpu.SP = stack_pointer.wrapping_sub(2);
+        self.mem.set(self.cpu.SP, IDLE_ADDR as u8);
+        self.mem.set(self.cpu.SP.wrapping_add(1), (IDLE_ADDR >> 8) as u8);
+        self.cpu.PC = addr;
+    }
+
+    // one frame worth of M-cycles with PLAY called at the track's rate
+    fn play_gbs_frame(&mut self) -> bool {
+        for _ in 0..GBS_FRAME_CYCLES {
+            if self.cpu.get_cycle() == 1 && self.breakpoints.contains(&self.cpu.PC) {
+                return false;
+            }
+            if self.cpu.fault().is_some() {
+                return false;
+            }
+
+            if self.mem.transfering {
+                self.mem.dma_transfer();
+            }
+
+            let idle = self.cpu.get_cycle() == 1 && self.cpu.PC == IDLE_ADDR;
+            let timer = self.mem.get_timer();
+            let gbs = self.gbs.as_mut().unwrap();
+            if gbs.tick(idle, timer.TMA, timer.TAC) {
+                let (play, stack_pointer) = (gbs.info.play_addr, gbs.info.stack_pointer);
+                self.call_gbs(play, stack_pointer);
+            }
+
+            self.cpu.check_interupts(&self.mem);
+            self.cpu.tick(&mut self.mem);
+            self.mem.tick();
+        }
+        true
+    }
+
     fn cycle(&mut self, check_break: bool) -> CycleState {
 
         if check_break && self.cpu.get_cycle() == 1 && self.breakpoints.contains(&self.cpu.PC) {
             return CycleState::Break;
         }
 
+        if check_break && self.cpu.fault().is_some() {
+            return CycleState::Fault;
+        }
+
         if self.mem.transfering {
             self.mem.dma_transfer();
         }
 
+        self.cpu.check_interupts(&self.mem);
+        if let Some(tracer) = self.tracer.as_mut() {
+            // a writer that fails once is dropped instead of failing every line after
+            if self.cpu.fetching() && tracer.log(&self.cpu, &self.mem).is_err() {
+                self.tracer = None;
+            }
+        }
+
+        self.mem.log_accesses(self.recording_step);
         self.cpu.tick(&mut self.mem);
-        self.ppu.tick(&mut self.mem);
+        self.mem.log_accesses(false);
+        // STOP halts the LCD along with the CPU
+        if !self.cpu.is_stop {
+            self.ppu.tick(&mut self.mem);
+        }
         self.mem.tick();
 
         return CycleState::Ran;
@@ -0,0 +1,249 @@
+use std::{env, fs, process};
+use std::io::{self, Write};
+
+use dmg_emu::Emu;
+use dmg_emu::cpu::CpuFault;
+use dmg_emu::recorder::Recording;
+
+const DEFAULT_FRAMES: u32 = 600;
+const WIDTH: usize = 160;
+const HEIGHT: usize = 144;
+
+const USAGE: &str = "usage: dmg_emu <rom or gbs> [options]
+
+  --frames N            stop after N frames (default 600)
+  --until-serial TEXT   stop once the serial output contains TEXT
+  --break ADDR          stop when PC reaches ADDR (hex), can be repeated
+  --frame-out PATH      write the final framebuffer as a PPM image
+  --wav PATH            record the audio of the run as a WAV file
+  --wav-channels        with --wav, also write each channel to PATH.ch1.wav to PATH.ch4.wav
+  --track N             GBS track to play, counting from 1
+  --trace PATH          log the registers before every instruction to PATH
+  --doctor              with --trace, start from the DMG boot registers with LY reading 0x90
+                        so the log lines up with gameboy-doctor
+  --serial              print the serial output
+  --cpu                 print the CPU registers
+
+exits with 1 when --until-serial is given and the text never shows up,
+a CPU lockup on an illegal opcode stops the run early";
+
+struct Options {
+    rom: String,
+    frames: u32,
+    until_serial: Option<String>,
+    breakpoints: Vec<u16>,
+    frame_out: Option<String>,
+    wav: Option<String>,
+    wav_channels: bool,
+    track: Option<u8>,
+    trace: Option<String>,
+    doctor: bool,
+    print_serial: bool,
+    print_cpu: bool,
+}
+
+enum Stop {
+    Frames,
+    Serial,
+    Breakpoint,
+    Fault(CpuFault),
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        rom: String::new(),
+        frames: DEFAULT_FRAMES,
+        until_serial: None,
+        breakpoints: vec![],
+        frame_out: None,
+        wav: None,
+        wav_channels: false,
+        track: None,
+        trace: None,
+        doctor: false,
+        print_serial: false,
+        print_cpu: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--frames" => {
+                let frames = value("--frames")?;
+                options.frames = frames.parse().map_err(|_| format!("invalid frame count {}", frames))?;
+            },
+            "--until-serial" => options.until_serial = Some(value("--until-serial")?),
+            "--break" => {
+                let addr = value("--break")?;
+                let parsed = u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("invalid address {}", addr))?;
+                options.breakpoints.push(parsed);
+            },
+            "--frame-out" => options.frame_out = Some(value("--frame-out")?),
+            "--wav" => options.wav = Some(value("--wav")?),
+            "--wav-channels" => options.wav_channels = true,
+            "--track" => {
+                let track = value("--track")?;
+                match track.parse::<u8>() {
+                    Ok(n) if n > 0 => options.track = Some(n - 1),
+                    _ => return Err(format!("invalid track {}", track)),
+                }
+            },
+            "--trace" => options.trace = Some(value("--trace")?),
+            "--doctor" => options.doctor = true,
+            "--serial" => options.print_serial = true,
+            "--cpu" => options.print_cpu = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.rom.is_empty() => options.rom = arg,
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    if options.rom.is_empty() {
+        return Err("no rom given".to_string());
+    }
+    if options.wav_channels && options.wav.is_none() {
+        return Err("--wav-channels needs --wav".to_string());
+    }
+    if options.doctor && options.trace.is_none() {
+        return Err("--doctor needs --trace".to_string());
+    }
+    Ok(options)
+}
+
+fn run(emu: &mut Emu, options: &Options) -> Stop {
+    emu.update_breakpoints(options.breakpoints.clone());
+    for _ in 0..options.frames {
+        if !emu.tick_till_frame_done() {
+            return match emu.get_cpu_fault() {
+                Some(fault) => Stop::Fault(fault),
+                None => Stop::Breakpoint,
+            };
+        }
+        if let Some(text) = &options.until_serial {
+            if emu.get_serial().contains(text.as_str()) {
+                return Stop::Serial;
+            }
+        }
+    }
+    Stop::Frames
+}
+
+// binary PPM, the alpha channel is dropped
+fn write_frame(path: &str, rgba: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    write!(file, "P6\n{} {}\n255\n", WIDTH, HEIGHT)?;
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|pixel| pixel[..3].to_vec()).collect();
+    file.write_all(&rgb)
+}
+
+fn write_wav(path: &str, recording: &Recording, channels: bool) -> io::Result<()> {
+    fs::write(path, recording.wav())?;
+    if channels {
+        for channel in 0..4 {
+            if let Some(wav) = recording.channel_wav(channel) {
+                fs::write(format!("{}.ch{}.wav", path, channel + 1), wav)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut emu = Emu::new();
+    let loaded = if options.rom.to_lowercase().ends_with(".gbs") {
+        let gbs = fs::read(&options.rom).unwrap_or_else(|e| {
+            eprintln!("could not read {}: {}", options.rom, e);
+            process::exit(2);
+        });
+        emu.load_gbs(gbs).map(|_| ())
+    } else {
+        emu.load_rom(&options.rom).map(|_| ())
+    };
+    if let Err(e) = loaded {
+        eprintln!("could not load {}: {}", options.rom, e);
+        process::exit(2);
+    }
+    emu.init();
+
+    if let Some(track) = options.track {
+        if let Err(e) = emu.select_track(track) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+
+    if let Some(path) = &options.trace {
+        let file = fs::File::create(path).unwrap_or_else(|e| {
+            eprintln!("could not create {}: {}", path, e);
+            process::exit(2);
+        });
+        emu.set_tracer(Box::new(io::BufWriter::new(file)));
+    }
+    if options.doctor {
+        // the state the DMG boot rom leaves behind
+        let cpu = emu.cpu();
+        cpu.AF = 0x01B0;
+        cpu.BC = 0x0013;
+        cpu.DE = 0x00D8;
+        cpu.HL = 0x014D;
+        emu.mem().stub_ly(Some(0x90));
+    }
+
+    if options.wav.is_some() {
+        emu.start_recording(options.wav_channels);
+    }
+    let stop = run(&mut emu, &options);
+    match stop {
+        Stop::Frames => println!("stopped after {} frames", options.frames),
+        Stop::Serial => println!("serial output matched"),
+        Stop::Breakpoint => println!("breakpoint hit at {:04X}", emu.cpu().PC),
+        Stop::Fault(fault) => println!("CPU locked up on illegal opcode {:02X} at {:04X}", fault.opcode, fault.pc),
+    }
+
+    emu.clear_tracer();
+
+    if options.print_serial {
+        println!("{}", emu.get_serial());
+    }
+
+    if options.print_cpu {
+        let cpu = emu.get_cpu_state();
+        println!(
+            "AF:{:04X} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} PC:{:04X} IME:{} HALT:{}",
+            cpu.AF, cpu.BC, cpu.DE, cpu.HL, cpu.SP, cpu.PC, cpu.ime as u8, cpu.halt as u8
+        );
+    }
+
+    if let Some(path) = &options.frame_out {
+        if let Err(e) = write_frame(path, &emu.get_buffer()) {
+            eprintln!("could not write {}: {}", path, e);
+            process::exit(2);
+        }
+    }
+
+    if let Some(path) = &options.wav {
+        let recording = emu.stop_recording().unwrap();
+        if let Err(e) = write_wav(path, &recording, options.wav_channels) {
+            eprintln!("could not write {}: {}", path, e);
+            process::exit(2);
+        }
+    }
+
+    // a breakpoint or a fault ends the run before the text showed up just the same
+    if options.until_serial.is_some() && !matches!(stop, Stop::Serial) {
+        process::exit(1);
+    }
+}
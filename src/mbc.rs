@@ -1,4 +1,8 @@
+use crate::error::EmuError;
+use crate::state::{Savable, StateReader, StateWriter};
 
+
+#[derive(PartialEq)]
 enum Hardware {
     RealTimeClock,
     Rumble,
@@ -6,18 +10,33 @@ enum Hardware {
     BatteryRam,
 }
 
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
 pub struct MBCProperties {
     rom_banks: u16,
     ram_total: u16,
     ram_banks: u16,
     features: Vec<Hardware>
 }
+
+impl MBCProperties {
+    fn has_battery(&self) -> bool {
+        self.features.contains(&Hardware::BatteryRam)
+    }
+}
 pub struct MBCBuilder {}
 
 impl MBCBuilder {
-    pub fn get_mbc_from_header(type_header: u8, rom_header: u8, ram_header: u8) -> Box<dyn MBC> {
+    pub fn get_mbc_from_header(type_header: u8, rom_header: u8, ram_header: u8) -> Result<Box<dyn MBC>, EmuError> {
         let rom_banks = MBCBuilder::get_rom_banks_from_header(rom_header);
-        let (ram_total, ram_banks) = MBCBuilder::get_ram_size_from_header(ram_header);
+        let (ram_total, ram_banks) = MBCBuilder::get_ram_size_from_header(ram_header)?;
         let features = MBCBuilder::get_features_from_header(type_header);
         let properties = MBCProperties {
             rom_banks,
@@ -28,9 +47,9 @@ impl MBCBuilder {
         MBCBuilder::get_mbc_type_from_header(type_header, properties)
     }
     
-    fn get_rom_banks_from_header(header: u8) -> u16 {
+    pub fn get_rom_banks_from_header(header: u8) -> u16 {
         match header {
-            0x00 => 1,
+            0x00 => 2,
             0x01 => 4,
             0x02 => 8,
             0x03 => 16,
@@ -46,15 +65,15 @@ impl MBCBuilder {
         }
     }
     //Returns: total, banks
-    fn get_ram_size_from_header(header: u8) -> (u16, u16) {
+    pub fn get_ram_size_from_header(header: u8) -> Result<(u16, u16), EmuError> {
         match header {
-            0x00 => (0, 0),
-            0x01 => (2, 1),
-            0x02 => (8, 1),
-            0x03 => (32, 4),
-            0x04 => (128, 16),
-            0x05 => (64, 8),
-            _ => panic!("Impossible to have {} for RAM", header)
+            0x00 => Ok((0, 0)),
+            0x01 => Ok((2, 1)),
+            0x02 => Ok((8, 1)),
+            0x03 => Ok((32, 4)),
+            0x04 => Ok((128, 16)),
+            0x05 => Ok((64, 8)),
+            _ => Err(EmuError::BadRamSize(header))
         }
     }
 
@@ -76,33 +95,29 @@ impl MBCBuilder {
             _ => vec![]
         }
     }
-    fn get_mbc_type_from_header(header: u8, properties: MBCProperties) -> Box<dyn MBC> {
+    fn get_mbc_type_from_header(header: u8, properties: MBCProperties) -> Result<Box<dyn MBC>, EmuError> {
         match header {
-            0x00 => Box::new(MBCNone::new(properties)),
-            0x01 => Box::new(MBC1::new(properties)),
-            0x02 =>	Box::new(MBC1::new(properties)),
-            0x03 =>	Box::new(MBC1::new(properties)),
-            0x06 =>	Box::new(MBC2::new(properties)),
-            0x05 =>	Box::new(MBC2::new(properties)),
-            0x08 =>	Box::new(MBCNone::new(properties)),
-            0x09 =>	Box::new(MBCNone::new(properties)),
-            0x0B =>	Box::new(MMM01::new(properties)), 
-            0x0C =>	Box::new(MMM01::new(properties)), 
-            0x0D =>	Box::new(MMM01::new(properties)), 
-            0x0F =>	Box::new(MBC3::new(properties)),
-            0x10 =>	Box::new(MBC3::new(properties)),
-            0x11 =>	Box::new(MBC3::new(properties)),
-            0x12 =>	Box::new(MBC3::new(properties)),
-            0x13 =>	Box::new(MBC3::new(properties)),
-            0x19 =>	Box::new(MBC5::new(properties)),
-            0x1A =>	Box::new(MBC5::new(properties)),
-            0x1B =>	Box::new(MBC5::new(properties)),
-            0x1C =>	Box::new(MBC5::new(properties)),
-            0x1D =>	Box::new(MBC5::new(properties)),
-            0x1E =>	Box::new(MBC5::new(properties)),
-            0x20 =>	Box::new(MBC6::new(properties)),
-            0x22 =>	Box::new(MBC7::new(properties)),
-            _ => panic!("Impossible to have {} for Type", header)
+            0x00 => Ok(Box::new(MBCNone::new(properties))),
+            0x01 => Ok(Box::new(MBC1::new(properties))),
+            0x02 =>	Ok(Box::new(MBC1::new(properties))),
+            0x03 =>	Ok(Box::new(MBC1::new(properties))),
+            0x06 =>	Ok(Box::new(MBC2::new(properties))),
+            0x05 =>	Ok(Box::new(MBC2::new(properties))),
+            0x08 =>	Ok(Box::new(MBCNone::new(properties))),
+            0x09 =>	Ok(Box::new(MBCNone::new(properties))),
+            0x0F =>	Ok(Box::new(MBC3::new(properties))),
+            0x10 =>	Ok(Box::new(MBC3::new(properties))),
+            0x11 =>	Ok(Box::new(MBC3::new(properties))),
+            0x12 =>	Ok(Box::new(MBC3::new(properties))),
+            0x13 =>	Ok(Box::new(MBC3::new(properties))),
+            0x19 =>	Ok(Box::new(MBC5::new(properties))),
+            0x1A =>	Ok(Box::new(MBC5::new(properties))),
+            0x1B =>	Ok(Box::new(MBC5::new(properties))),
+            0x1C =>	Ok(Box::new(MBC5::new(properties))),
+            0x1D =>	Ok(Box::new(MBC5::new(properties))),
+            0x1E =>	Ok(Box::new(MBC5::new(properties))),
+            // MMM01, MBC6, MBC7 and the camera/tama/huc carts are not emulated
+            _ => Err(EmuError::UnsupportedMapper(header))
         }
     }
 
@@ -111,16 +126,83 @@ impl MBCBuilder {
     }
 }
 
-pub trait MBC {
+pub trait MBC: Savable {
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, v: u8);
     fn load_cart(&mut self, rom: Vec<u8>);
+
+    // called once per M-cycle for carts with their own clocked hardware
+    fn tick(&mut self) {}
+
+    // the ROM bank currently mapped at `addr`, which is below 0x8000
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 { 0 } else { 1 }
+    }
+
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    // every change of the rumble motor since the last call, oldest first
+    fn take_rumble_events(&mut self) -> Vec<bool> {
+        vec![]
+    }
+
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    // cartridge RAM in the raw .sav layout, `unix_time` is when the save is made
+    fn save_ram(&self, _unix_time: f64) -> Vec<u8> {
+        vec![]
+    }
+
+    fn load_ram(&mut self, _data: &[u8]) {}
+
+    // true when RAM was written since the last save_ram
+    fn ram_dirty(&self) -> bool {
+        false
+    }
+
+    fn clear_ram_dirty(&mut self) {}
+}
+
+// the bank count comes from the header so it is not stored
+fn save_banks(banks: &[[u8; RAM_BANK_SIZE]], state: &mut StateWriter) {
+    for bank in banks {
+        state.bytes(bank);
+    }
+}
+
+fn load_banks(banks: &mut [[u8; RAM_BANK_SIZE]], state: &mut StateReader) -> Result<(), EmuError> {
+    for bank in banks {
+        state.bytes(bank)?;
+    }
+    Ok(())
+}
+
+// flattens banked RAM into one buffer, 2 KiB chips only store 2 KiB
+fn banks_to_bytes(banks: &[[u8; RAM_BANK_SIZE]], ram_total: u16) -> Vec<u8> {
+    let mut data: Vec<u8> = banks.iter().flat_map(|bank| bank.iter().copied()).collect();
+    data.truncate(ram_total as usize * 1024);
+    data
+}
+
+fn bytes_to_banks(banks: &mut [[u8; RAM_BANK_SIZE]], data: &[u8]) {
+    for (bank, data) in banks.iter_mut().zip(data.chunks(RAM_BANK_SIZE)) {
+        bank[..data.len()].copy_from_slice(data);
+    }
 }
 
 pub struct MBCNone {
     properties: MBCProperties,
     rom_bank: [u8; 1024 * 32],
     ram_bank: [u8; 1024 * 16],
+    ram_dirty: bool,
 }
 
 impl MBCNone {
@@ -129,8 +211,13 @@ impl MBCNone {
             properties,
             rom_bank: [0x0; 1024 * 32],
             ram_bank: [0x0; 1024 * 16],
+            ram_dirty: false,
         }
     }
+
+    fn ram_size(&self) -> usize {
+        (self.properties.ram_total as usize * 1024).min(RAM_BANK_SIZE)
+    }
 }
 
 impl MBC for MBCNone {
@@ -138,35 +225,79 @@ impl MBC for MBCNone {
         match addr {
             0x0000..=0x7FFF => self.rom_bank[addr as usize],
             0xA000..=0xBFFF => {
-                let ram_size = self.properties.ram_total * 1024;
-                let ram_pos = 0xA000 - addr;
-                if ram_pos > ram_size {
+                let ram_pos = (addr - 0xA000) as usize;
+                if ram_pos >= self.ram_size() {
                     return 0xFF;
                 }
-                self.ram_bank[ram_pos as usize]
+                self.ram_bank[ram_pos]
             },
             _ => 0xFF
         }
     }
 
-    fn write(&mut self, _addr: u16, _value: u8) {
-        return;
+    fn write(&mut self, addr: u16, value: u8) {
+        if let 0xA000..=0xBFFF = addr {
+            let ram_pos = (addr - 0xA000) as usize;
+            if ram_pos < self.ram_size() {
+                self.ram_bank[ram_pos] = value;
+                self.ram_dirty = true;
+            }
+        }
     }
 
     fn load_cart(&mut self, rom: Vec<u8>) {
-        for (i, data) in rom.iter().enumerate() {
+        for (i, data) in rom.iter().take(self.rom_bank.len()).enumerate() {
             self.rom_bank[i] = *data;
         }
     }
+
+    fn has_battery(&self) -> bool {
+        self.properties.has_battery()
+    }
+
+    fn save_ram(&self, _unix_time: f64) -> Vec<u8> {
+        self.ram_bank[..self.ram_size()].to_vec()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram_size());
+        self.ram_bank[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn ram_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    fn clear_ram_dirty(&mut self) {
+        self.ram_dirty = false;
+    }
+}
+
+impl Savable for MBCNone {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.ram_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        state.bytes(&mut self.ram_bank)?;
+        self.ram_dirty = true;
+        Ok(())
+    }
 }
 
 pub struct MBC1 {
     properties: MBCProperties,
-    active_rom: u8,
-    active_ram: u8,
+    // 5 bit BANK1 register (0x2000-0x3FFF)
+    bank1: u8,
+    // 2 bit BANK2 register (0x4000-0x5FFF)
+    bank2: u8,
+    advanced_mode: bool,
     ram_enabled: bool,
-    rom_banks: Vec<[u8; 16384]>,
-    ram_banks: Vec<[u8; 16384]>,
+    // MBC1M carts only wire 4 bits of BANK1 to the ROM
+    multicart: bool,
+    rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+    ram_banks: Vec<[u8; RAM_BANK_SIZE]>,
+    ram_dirty: bool,
 }
 
 impl MBC1 {
@@ -175,30 +306,72 @@ impl MBC1 {
         let rom_banks = properties.rom_banks;
         Self {
             properties,
-            active_rom: 1,
-            active_ram: 1,
+            bank1: 1,
+            bank2: 0,
+            advanced_mode: false,
             ram_enabled: false,
-            rom_banks: vec![[0x0; 16384]; rom_banks as usize],
-            ram_banks: vec![[0x0; 16384]; ram_banks as usize],
+            multicart: false,
+            rom_banks: vec![[0x0; ROM_BANK_SIZE]; rom_banks as usize],
+            ram_banks: vec![[0x0; RAM_BANK_SIZE]; ram_banks as usize],
+            ram_dirty: false,
+        }
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if !self.advanced_mode {
+            return 0;
+        }
+        let bank = (self.bank2 << self.bank2_shift()) as usize;
+        bank % self.rom_banks.len()
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+        let bank = ((self.bank2 << self.bank2_shift()) | bank1) as usize;
+        bank % self.rom_banks.len()
+    }
+
+    fn ram_bank(&self) -> usize {
+        if !self.advanced_mode {
+            return 0;
         }
+        self.bank2 as usize % self.ram_banks.len()
+    }
+
+    fn ram_addr(&self, addr: u16) -> usize {
+        let ram_addr = (addr - 0xA000) as usize;
+        // a 2 KiB chip repeats itself across the whole 8 KiB window
+        ram_addr % (self.properties.ram_total as usize * 1024).min(RAM_BANK_SIZE)
+    }
+
+    // MBC1M collections are 1 MiB carts with a second Nintendo logo at bank 0x10
+    fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != 64 * ROM_BANK_SIZE {
+            return false;
+        }
+        let logo_addr = 0x10 * ROM_BANK_SIZE + 0x0104;
+        rom[logo_addr..logo_addr + NINTENDO_LOGO.len()] == NINTENDO_LOGO
     }
 }
 
 impl MBC for MBC1 {
+    fn rom_bank(&self, addr: u16) -> u16 {
+        match addr {
+            0x0000..=0x3FFF => self.low_rom_bank() as u16,
+            _ => self.high_rom_bank() as u16,
+        }
+    }
+
     fn read(&self, addr: u16) -> u8 {
         match addr {
-            0x0000..=0x3FFF => self.rom_banks[0][addr as usize],
-            0x4000..=0x7FFF => self.rom_banks[self.active_rom as usize][(addr - 0x4000) as usize],
-            0xA000..=0xBFFF => {
-                if self.ram_enabled {
-                    let mut ram_addr = (addr - 0xA000) as usize;
-                    if self.properties.ram_total == 2 || self.properties.ram_total == 8 {
-                        ram_addr = ram_addr % self.properties.ram_total as usize;
-                    }
-                    self.ram_banks[self.active_ram as usize][ram_addr]
-                } else {
-                    0xFF
-                }
+            0x0000..=0x3FFF => self.rom_banks[self.low_rom_bank()][addr as usize],
+            0x4000..=0x7FFF => self.rom_banks[self.high_rom_bank()][(addr - 0x4000) as usize],
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram_banks.is_empty() => {
+                self.ram_banks[self.ram_bank()][self.ram_addr(addr)]
             },
             _ => 0xFF
         }
@@ -206,207 +379,722 @@ impl MBC for MBC1 {
 
     fn write(&mut self, addr: u16, value: u8) {
         match addr {
-            0x0000..=0x1FFF => if value & 0x0F == 0x0A {self.ram_enabled = true} else {self.ram_enabled = false}
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
             0x2000..=0x3FFF => {
-                let mask = self.properties.ram_banks - 1;
-            }
-            0xA000..=0xBFFF => {
-                if self.ram_enabled {
-                    let mut ram_addr = (addr - 0xA000) as usize;
-                    if self.properties.ram_total == 2 || self.properties.ram_total == 8 {
-                        ram_addr = ram_addr % self.properties.ram_total as usize;
-                    }
-                    self.ram_banks[self.active_ram as usize][ram_addr] = value;
+                self.bank1 = value & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
                 }
             },
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.advanced_mode = value & 1 == 1,
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram_banks.is_empty() => {
+                let bank = self.ram_bank();
+                let ram_addr = self.ram_addr(addr);
+                self.ram_banks[bank][ram_addr] = value;
+                self.ram_dirty = true;
+            },
             _ => ()
         }
     }
 
     fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+        self.multicart = MBC1::is_multicart(&rom);
+        for (bank, data) in self.rom_banks.iter_mut().zip(rom.chunks(ROM_BANK_SIZE)) {
+            bank[..data.len()].copy_from_slice(data);
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.properties.has_battery()
+    }
+
+    fn save_ram(&self, _unix_time: f64) -> Vec<u8> {
+        banks_to_bytes(&self.ram_banks, self.properties.ram_total)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        bytes_to_banks(&mut self.ram_banks, data);
+    }
+
+    fn ram_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    fn clear_ram_dirty(&mut self) {
+        self.ram_dirty = false;
+    }
+}
+
+impl Savable for MBC1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank1);
+        state.u8(self.bank2);
+        state.bool(self.advanced_mode);
+        state.bool(self.ram_enabled);
+        save_banks(&self.ram_banks, state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.bank1 = state.u8()?;
+        self.bank2 = state.u8()?;
+        self.advanced_mode = state.bool()?;
+        self.ram_enabled = state.bool()?;
+        load_banks(&mut self.ram_banks, state)?;
+        self.ram_dirty = true;
+        Ok(())
     }
 }
 
+const MBC2_RAM_SIZE: usize = 512;
+
 pub struct MBC2 {
     properties: MBCProperties,
+    rom_bank: u8,
+    ram_enabled: bool,
+    rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+    // only the low nibble of each byte exists on the chip
+    ram: [u8; MBC2_RAM_SIZE],
+    ram_dirty: bool,
 }
 
 impl MBC2 {
     pub fn new(properties: MBCProperties) -> Self {
+        let rom_banks = properties.rom_banks;
         Self {
-            properties
+            properties,
+            rom_bank: 1,
+            ram_enabled: false,
+            rom_banks: vec![[0x0; ROM_BANK_SIZE]; rom_banks as usize],
+            ram: [0x0; MBC2_RAM_SIZE],
+            ram_dirty: false,
         }
     }
 }
 
 impl MBC for MBC2 {
+    fn rom_bank(&self, addr: u16) -> u16 {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => (self.rom_bank as usize % self.rom_banks.len()) as u16,
+        }
+    }
+
     fn read(&self, addr: u16) -> u8 {
-        todo!()
+        match addr {
+            0x0000..=0x3FFF => self.rom_banks[0][addr as usize],
+            0x4000..=0x7FFF => {
+                let bank = self.rom_bank as usize % self.rom_banks.len();
+                self.rom_banks[bank][(addr - 0x4000) as usize]
+            },
+            // the 512 half-bytes echo across the whole RAM window
+            0xA000..=0xBFFF if self.ram_enabled => 0xF0 | self.ram[addr as usize & 0x1FF],
+            _ => 0xFF
+        }
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            // address bit 8 picks the register
+            0x0000..=0x3FFF => {
+                if addr & 0x100 == 0 {
+                    self.ram_enabled = value & 0x0F == 0x0A;
+                } else {
+                    self.rom_bank = value & 0x0F;
+                    if self.rom_bank == 0 {
+                        self.rom_bank = 1;
+                    }
+                }
+            },
+            0xA000..=0xBFFF if self.ram_enabled => {
+                self.ram[addr as usize & 0x1FF] = value & 0x0F;
+                self.ram_dirty = true;
+            },
+            _ => ()
+        }
     }
 
     fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+        for (bank, data) in self.rom_banks.iter_mut().zip(rom.chunks(ROM_BANK_SIZE)) {
+            bank[..data.len()].copy_from_slice(data);
+        }
     }
-}
 
-pub struct MBC3 {
-    properties: MBCProperties
-}
+    fn has_battery(&self) -> bool {
+        self.properties.has_battery()
+    }
 
-impl MBC3 {
-    pub fn new(properties: MBCProperties) -> Self {
-        Self {
-            properties
+    fn save_ram(&self, _unix_time: f64) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        for (cell, value) in self.ram.iter_mut().zip(data) {
+            *cell = value & 0x0F;
         }
     }
-}
 
-impl MBC for MBC3 {
-    fn read(&self, addr: u16) -> u8 {
-        todo!()
+    fn ram_dirty(&self) -> bool {
+        self.ram_dirty
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    fn clear_ram_dirty(&mut self) {
+        self.ram_dirty = false;
     }
+}
 
-    fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+impl Savable for MBC2 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.bool(self.ram_enabled);
+        state.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.rom_bank = state.u8()?;
+        self.ram_enabled = state.bool()?;
+        state.bytes(&mut self.ram)?;
+        self.ram_dirty = true;
+        Ok(())
     }
 }
 
-pub struct MBC4 {
-    properties: MBCProperties
+// the RTC counts seconds off the 32.768 KHz crystal, one second every 2^20 M-cycles
+const RTC_CYCLES_PER_SECOND: u32 = 1_048_576;
+const RTC_TRAILER_SIZE: usize = 48;
+
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    carry: bool,
+    cycles: u32,
+    host_clock: bool,
+    last_sync: f64,
 }
 
-impl MBC4 {
-    pub fn new(properties: MBCProperties) -> Self {
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rtc {
+    pub fn new() -> Self {
         Self {
-            properties
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            carry: false,
+            cycles: 0,
+            host_clock: false,
+            last_sync: 0.0,
         }
     }
-}
 
-impl MBC for MBC4 {
-    fn read(&self, addr: u16) -> u8 {
-        todo!()
+    // 0x08 - 0x0C as seen through the cartridge RAM window
+    pub fn read(&self, reg: u8) -> u8 {
+        match reg {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days as u8,
+            0x0C => (self.carry as u8) << 7 | (self.halt as u8) << 6 | (self.days >> 8) as u8,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, reg: u8, value: u8) {
+        match reg {
+            0x08 => {
+                self.seconds = value & 0x3F;
+                self.cycles = 0;
+            },
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | value as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | ((value as u16 & 1) << 8);
+                self.halt = value >> 6 & 1 == 1;
+                self.carry = value >> 7 & 1 == 1;
+            },
+            _ => ()
+        }
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    pub fn tick(&mut self) {
+        if self.halt || self.host_clock {
+            return;
+        }
+        self.cycles += 1;
+        if self.cycles == RTC_CYCLES_PER_SECOND {
+            self.cycles = 0;
+            self.tick_second();
+        }
     }
 
-    fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+    // Runs the clock off the host instead of emulated cycles, time only
+    // moves forward when `sync` is called with the current unix time
+    pub fn use_host_clock(&mut self, enabled: bool) {
+        self.host_clock = enabled;
     }
-}
 
-pub struct MBC5 {
-    properties: MBCProperties
-}
+    pub fn sync(&mut self, unix_time: f64) {
+        if !self.host_clock {
+            return;
+        }
+        if self.last_sync == 0.0 || unix_time < self.last_sync {
+            self.last_sync = unix_time;
+            return;
+        }
+        let elapsed = (unix_time - self.last_sync).floor();
+        self.last_sync += elapsed;
+        if !self.halt {
+            self.advance(elapsed as u64);
+        }
+    }
 
-impl MBC5 {
-    pub fn new(properties: MBCProperties) -> Self {
-        Self {
-            properties
+    pub fn advance(&mut self, mut seconds: u64) {
+        // registers set out of range by software tick over at their bit width
+        // rather than carrying, so walk one second at a time until they're sane
+        while seconds > 0 && (self.seconds > 59 || self.minutes > 59 || self.hours > 23) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        let total = seconds
+            + self.seconds as u64
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400;
+        let days = total / 86400;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        if days > 0x1FF {
+            self.carry = true;
         }
+        self.days = (days & 0x1FF) as u16;
     }
-}
 
-impl MBC for MBC5 {
-    fn read(&self, addr: u16) -> u8 {
-        todo!()
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days > 0x1FF {
+            self.days = 0;
+            self.carry = true;
+        }
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    // the current then latched registers as 32 bit little endian words,
+    // followed by the unix time of the save as a 64 bit word
+    fn save_trailer(&self, latched: &[u8; 5], unix_time: f64) -> Vec<u8> {
+        let mut trailer = Vec::with_capacity(RTC_TRAILER_SIZE);
+        for reg in self.latch().iter().chain(latched.iter()) {
+            trailer.extend_from_slice(&(*reg as u32).to_le_bytes());
+        }
+        // a host synced clock holds the registers as of its last sync
+        let saved_at = if self.host_clock && self.last_sync != 0.0 { self.last_sync } else { unix_time };
+        trailer.extend_from_slice(&(saved_at as u64).to_le_bytes());
+        trailer
     }
 
-    fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+    fn load_trailer(&mut self, trailer: &[u8]) -> [u8; 5] {
+        let word = |i: usize| trailer[i * 4];
+        for (i, reg) in (0x08..=0x0C).enumerate() {
+            self.write(reg, word(i));
+        }
+        let mut latched = [0; 5];
+        for (i, reg) in latched.iter_mut().enumerate() {
+            *reg = word(i + 5);
+        }
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&trailer[40..48]);
+        // a host synced clock catches up from here on the next sync
+        self.last_sync = u64::from_le_bytes(timestamp) as f64;
+        latched
+    }
+
+    fn latch(&self) -> [u8; 5] {
+        [self.read(0x08), self.read(0x09), self.read(0x0A), self.read(0x0B), self.read(0x0C)]
     }
 }
 
-pub struct MBC6 {
-    properties: MBCProperties
+impl Savable for Rtc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.seconds);
+        state.u8(self.minutes);
+        state.u8(self.hours);
+        state.u16(self.days);
+        state.bool(self.halt);
+        state.bool(self.carry);
+        state.u32(self.cycles);
+        state.bool(self.host_clock);
+        state.f64(self.last_sync);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.seconds = state.u8()?;
+        self.minutes = state.u8()?;
+        self.hours = state.u8()?;
+        self.days = state.u16()?;
+        self.halt = state.bool()?;
+        self.carry = state.bool()?;
+        self.cycles = state.u32()?;
+        self.host_clock = state.bool()?;
+        self.last_sync = state.f64()?;
+        Ok(())
+    }
+}
+
+pub struct MBC3 {
+    properties: MBCProperties,
+    rom_bank: u8,
+    // 0x00-0x07 selects a RAM bank, 0x08-0x0C an RTC register
+    ram_select: u8,
+    ram_enabled: bool,
+    rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+    ram_banks: Vec<[u8; RAM_BANK_SIZE]>,
+    rtc: Rtc,
+    latched: [u8; 5],
+    last_latch_write: u8,
+    ram_dirty: bool,
 }
 
-impl MBC6 {
+impl MBC3 {
     pub fn new(properties: MBCProperties) -> Self {
+        let ram_banks = properties.ram_banks;
+        let rom_banks = properties.rom_banks;
         Self {
-            properties
+            properties,
+            rom_bank: 1,
+            ram_select: 0,
+            ram_enabled: false,
+            rom_banks: vec![[0x0; ROM_BANK_SIZE]; rom_banks as usize],
+            ram_banks: vec![[0x0; RAM_BANK_SIZE]; ram_banks as usize],
+            rtc: Rtc::new(),
+            latched: [0; 5],
+            last_latch_write: 0xFF,
+            ram_dirty: false,
         }
     }
+
+    fn has_rtc(&self) -> bool {
+        self.properties.features.contains(&Hardware::RealTimeClock)
+    }
+
+    fn ram_addr(&self, addr: u16) -> usize {
+        let ram_addr = (addr - 0xA000) as usize;
+        ram_addr % (self.properties.ram_total as usize * 1024).min(RAM_BANK_SIZE)
+    }
 }
 
-impl MBC for MBC6 {
+impl MBC for MBC3 {
+    fn rom_bank(&self, addr: u16) -> u16 {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => (self.rom_bank as usize % self.rom_banks.len()) as u16,
+        }
+    }
+
     fn read(&self, addr: u16) -> u8 {
-        todo!()
+        match addr {
+            0x0000..=0x3FFF => self.rom_banks[0][addr as usize],
+            0x4000..=0x7FFF => {
+                let bank = self.rom_bank as usize % self.rom_banks.len();
+                self.rom_banks[bank][(addr - 0x4000) as usize]
+            },
+            0xA000..=0xBFFF if self.ram_enabled => match self.ram_select {
+                0x00..=0x07 if !self.ram_banks.is_empty() => {
+                    let bank = self.ram_select as usize % self.ram_banks.len();
+                    self.ram_banks[bank][self.ram_addr(addr)]
+                },
+                0x08..=0x0C if self.has_rtc() => self.latched[(self.ram_select - 0x08) as usize],
+                _ => 0xFF
+            },
+            _ => 0xFF
+        }
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = value & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            },
+            0x4000..=0x5FFF => self.ram_select = value & 0x0F,
+            0x6000..=0x7FFF => {
+                if self.last_latch_write == 0x00 && value == 0x01 {
+                    self.latched = self.rtc.latch();
+                }
+                self.last_latch_write = value;
+            },
+            0xA000..=0xBFFF if self.ram_enabled => match self.ram_select {
+                0x00..=0x07 if !self.ram_banks.is_empty() => {
+                    let bank = self.ram_select as usize % self.ram_banks.len();
+                    let ram_addr = self.ram_addr(addr);
+                    self.ram_banks[bank][ram_addr] = value;
+                    self.ram_dirty = true;
+                },
+                0x08..=0x0C if self.has_rtc() => {
+                    self.rtc.write(self.ram_select, value);
+                    self.latched[(self.ram_select - 0x08) as usize] = self.rtc.read(self.ram_select);
+                    self.ram_dirty = true;
+                },
+                _ => ()
+            },
+            _ => ()
+        }
     }
 
     fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+        for (bank, data) in self.rom_banks.iter_mut().zip(rom.chunks(ROM_BANK_SIZE)) {
+            bank[..data.len()].copy_from_slice(data);
+        }
     }
-}
 
-pub struct MBC7 {
-    properties: MBCProperties
-}
+    fn tick(&mut self) {
+        if self.has_rtc() {
+            self.rtc.tick();
+        }
+    }
 
-impl MBC7 {
-    pub fn new(properties: MBCProperties) -> Self {
-        Self {
-            properties
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        if self.has_rtc() {
+            Some(&mut self.rtc)
+        } else {
+            None
         }
     }
-}
 
-impl MBC for MBC7 {
-    fn read(&self, addr: u16) -> u8 {
-        todo!()
+    fn has_battery(&self) -> bool {
+        self.properties.has_battery()
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    // RTC carts append the clock in the 48 byte trailer used by VBA and BGB
+    fn save_ram(&self, unix_time: f64) -> Vec<u8> {
+        let mut data = banks_to_bytes(&self.ram_banks, self.properties.ram_total);
+        if self.has_rtc() {
+            data.extend(self.rtc.save_trailer(&self.latched, unix_time));
+        }
+        data
     }
 
-    fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+    fn load_ram(&mut self, data: &[u8]) {
+        let ram_size = self.properties.ram_total as usize * 1024;
+        let (ram, trailer) = data.split_at(ram_size.min(data.len()));
+        bytes_to_banks(&mut self.ram_banks, ram);
+        if self.has_rtc() && trailer.len() >= RTC_TRAILER_SIZE {
+            self.latched = self.rtc.load_trailer(trailer);
+        }
+    }
+
+    fn ram_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    fn clear_ram_dirty(&mut self) {
+        self.ram_dirty = false;
     }
 }
 
-pub struct MMM01 {
-    properties: MBCProperties
+impl Savable for MBC3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.u8(self.ram_select);
+        state.bool(self.ram_enabled);
+        save_banks(&self.ram_banks, state);
+        self.rtc.save_state(state);
+        state.bytes(&self.latched);
+        state.u8(self.last_latch_write);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.rom_bank = state.u8()?;
+        self.ram_select = state.u8()?;
+        self.ram_enabled = state.bool()?;
+        load_banks(&mut self.ram_banks, state)?;
+        self.rtc.load_state(state)?;
+        state.bytes(&mut self.latched)?;
+        self.last_latch_write = state.u8()?;
+        self.ram_dirty = true;
+        Ok(())
+    }
 }
 
-impl MMM01 {
+// keeps an undrained rumble log from growing without bound
+const MAX_RUMBLE_EVENTS: usize = 1024;
+
+pub struct MBC5 {
+    properties: MBCProperties,
+    rom_bank: u16,
+    ram_bank: u8,
+    ram_enabled: bool,
+    rumble: bool,
+    rumble_events: Vec<bool>,
+    rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+    ram_banks: Vec<[u8; RAM_BANK_SIZE]>,
+    ram_dirty: bool,
+}
+
+impl MBC5 {
     pub fn new(properties: MBCProperties) -> Self {
+        let ram_banks = properties.ram_banks;
+        let rom_banks = properties.rom_banks;
         Self {
-            properties
+            properties,
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rumble: false,
+            rumble_events: vec![],
+            rom_banks: vec![[0x0; ROM_BANK_SIZE]; rom_banks as usize],
+            ram_banks: vec![[0x0; RAM_BANK_SIZE]; ram_banks as usize],
+            ram_dirty: false,
         }
     }
+
+    fn has_rumble(&self) -> bool {
+        self.properties.features.contains(&Hardware::Rumble)
+    }
+
+    fn ram_addr(&self, addr: u16) -> usize {
+        let ram_addr = (addr - 0xA000) as usize;
+        ram_addr % (self.properties.ram_total as usize * 1024).min(RAM_BANK_SIZE)
+    }
+
+    fn set_rumble(&mut self, on: bool) {
+        if self.rumble == on {
+            return;
+        }
+        self.rumble = on;
+        if self.rumble_events.len() == MAX_RUMBLE_EVENTS {
+            self.rumble_events.remove(0);
+        }
+        self.rumble_events.push(on);
+    }
 }
 
-impl MBC for MMM01 {
+impl MBC for MBC5 {
+    fn rom_bank(&self, addr: u16) -> u16 {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => (self.rom_bank as usize % self.rom_banks.len()) as u16,
+        }
+    }
+
     fn read(&self, addr: u16) -> u8 {
-        todo!()
+        match addr {
+            0x0000..=0x3FFF => self.rom_banks[0][addr as usize],
+            0x4000..=0x7FFF => {
+                let bank = self.rom_bank as usize % self.rom_banks.len();
+                self.rom_banks[bank][(addr - 0x4000) as usize]
+            },
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram_banks.is_empty() => {
+                let bank = self.ram_bank as usize % self.ram_banks.len();
+                self.ram_banks[bank][self.ram_addr(addr)]
+            },
+            _ => 0xFF
+        }
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // unlike the older MBCs bank 0 can be mapped into 0x4000-0x7FFF
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 1) << 8),
+            0x4000..=0x5FFF => {
+                if self.has_rumble() {
+                    // the motor is wired to bit 3 so only 8 RAM banks are addressable
+                    self.set_rumble(value >> 3 & 1 == 1);
+                    self.ram_bank = value & 0x07;
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+            },
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram_banks.is_empty() => {
+                let bank = self.ram_bank as usize % self.ram_banks.len();
+                let ram_addr = self.ram_addr(addr);
+                self.ram_banks[bank][ram_addr] = value;
+                self.ram_dirty = true;
+            },
+            _ => ()
+        }
     }
 
     fn load_cart(&mut self, rom: Vec<u8>) {
-        todo!()
+        for (bank, data) in self.rom_banks.iter_mut().zip(rom.chunks(ROM_BANK_SIZE)) {
+            bank[..data.len()].copy_from_slice(data);
+        }
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn take_rumble_events(&mut self) -> Vec<bool> {
+        std::mem::take(&mut self.rumble_events)
+    }
+
+    fn has_battery(&self) -> bool {
+        self.properties.has_battery()
+    }
+
+    fn save_ram(&self, _unix_time: f64) -> Vec<u8> {
+        banks_to_bytes(&self.ram_banks, self.properties.ram_total)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        bytes_to_banks(&mut self.ram_banks, data);
+    }
+
+    fn ram_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    fn clear_ram_dirty(&mut self) {
+        self.ram_dirty = false;
+    }
+}
+
+impl Savable for MBC5 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.bool(self.ram_enabled);
+        state.bool(self.rumble);
+        save_banks(&self.ram_banks, state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.rom_bank = state.u16()?;
+        self.ram_bank = state.u8()?;
+        self.ram_enabled = state.bool()?;
+        self.rumble = state.bool()?;
+        load_banks(&mut self.ram_banks, state)?;
+        self.ram_dirty = true;
+        Ok(())
     }
 }
 
@@ -419,15 +1107,20 @@ impl MBCUndefined {
 }
 
 impl MBC for MBCUndefined {
-    fn read(&self, addr: u16) -> u8 {
-        todo!()
+    // no cartridge inserted, the bus floats high
+    fn read(&self, _addr: u16) -> u8 {
+        0xFF
     }
 
-    fn write(&mut self, addr: u16, v: u8) {
-        todo!()
-    }
+    fn write(&mut self, _addr: u16, _v: u8) {}
 
-    fn load_cart(&mut self, rom: Vec<u8>) {
-        panic!("Never initialized the MBC");
+    fn load_cart(&mut self, _rom: Vec<u8>) {}
+}
+
+impl Savable for MBCUndefined {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), EmuError> {
+        Ok(())
     }
 }
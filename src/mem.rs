@@ -1,7 +1,13 @@
 
 use crate::io::{Button, Joypad, P1_ADDR, SB_ADDR, SC_ADDR, Serial, Timer, DIV_ADDR, TIMA_ADDR, TMA_ADDR, TAC_ADDR};
 use crate::apu::APU;
-use crate::mbc::{MBCBuilder, MBC};
+use crate::bus::Bus;
+use crate::recorder::Recording;
+use crate::cartridge::CartridgeInfo;
+use crate::error::EmuError;
+use crate::mbc::{MBCBuilder, MBC, Rtc};
+use crate::state::{Savable, StateReader, StateWriter};
+use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
 
 extern crate web_sys;
@@ -13,6 +19,17 @@ macro_rules! log {
     }
 }
 
+// one read or write made by the CPU while an instruction step is recorded
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemAccess {
+    // M-cycle of the step it happened in, counting from 1
+    pub cycle: u8,
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
 #[wasm_bindgen]
 
 pub struct Mem {
@@ -26,6 +43,8 @@ pub struct Mem {
     scrolly: u8,
     scrollx: u8,
     ly: u8,
+    // gameboy-doctor logs are taken with LY stuck at 0x90
+    ly_stub: Option<u8>,
     lyc: u8,
     dma: u8,
     bgp: u8,
@@ -44,8 +63,10 @@ pub struct Mem {
 
     pub ppu_access: bool,
     rom_lock: bool,
-    mbc: Box<dyn MBC>
+    mbc: Box<dyn MBC>,
 
+    log_accesses: bool,
+    access_log: RefCell<Vec<MemAccess>>,
 }
 
 impl Mem {
@@ -60,6 +81,7 @@ impl Mem {
             scrolly: 0,
             scrollx: 0,
             ly: 0,
+            ly_stub: None,
             lyc: 0,
             dma: 0,
             bgp: 0,
@@ -78,7 +100,10 @@ impl Mem {
 
             ppu_access: false,
             rom_lock: false,
-            mbc: Box::new(MBCBuilder::undefined())
+            mbc: Box::new(MBCBuilder::undefined()),
+
+            log_accesses: false,
+            access_log: RefCell::new(vec![]),
         }
     }
 
@@ -94,6 +119,10 @@ impl Mem {
         &self.serial
     }
 
+    pub fn get_apu(&self) -> &APU {
+        &self.apu
+    }
+
     pub fn get_timer(&self) -> Timer {
         self.timer.clone()
     }
@@ -103,6 +132,15 @@ impl Mem {
     }
 
     pub fn get(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        if self.log_accesses {
+            self.access_log.borrow_mut().push(MemAccess { cycle: 0, addr, value, write: false });
+        }
+        value
+    }
+
+    // same as get but never shows up in the access log
+    pub fn peek(&self, addr: u16) -> u8 {
         if self.transfering && !self.ppu_access { // can only access high ram
             return match addr {
                 0xFF80..=0xFFFE => self.mem[addr as usize],
@@ -113,12 +151,15 @@ impl Mem {
         match addr {
             P1_ADDR => self.joypad.read(),
             0xFFFF => self.ienable,
-            0xFF0F => self.iflag,
+            // only 5 interrupt lines, the unused bits read high
+            0xFF0F => self.iflag | 0xE0,
 
             0xFF40 => self.LCDControl,
             0xFF41 => self.LCDStatus,
             0xFF42 => self.scrolly,
             0xFF43 => self.scrollx,
+            // the PPU still sees the real scanline
+            0xFF44 if !self.ppu_access => self.ly_stub.unwrap_or(self.ly),
             0xFF44 => self.ly,
             0xFF45 => self.lyc,
             0xFF46 => self.dma,
@@ -132,8 +173,8 @@ impl Mem {
             SB_ADDR | SC_ADDR => self.serial.read(addr),
             0xFF10..=0xFF3F => self.apu.read(addr),
             0xE000 ..= 0xFDFF => self.mem[(addr - 0x200) as usize],
-            0xA000..=0xBFFF => self.mbc.read(addr),
-            0x0000..=0x7FFF => self.mbc.read(addr),
+            0xA000..=0xBFFF if self.rom_lock => self.mbc.read(addr),
+            0x0000..=0x7FFF if self.rom_lock => self.mbc.read(addr),
             0x0000 ..= 0xFFFE => self.mem[addr as usize],
             _ => 0xFF
         }
@@ -153,10 +194,13 @@ impl Mem {
     }
 
     pub fn set(&mut self, addr: u16, value: u8) {
+        if self.log_accesses {
+            self.access_log.borrow_mut().push(MemAccess { cycle: 0, addr, value, write: true });
+        }
 
         match addr {
             P1_ADDR => self.joypad.write(value),
-            0xFF0F => self.iflag = value,
+            0xFF0F => self.iflag = value & 0x1F,
             
             0xFF40 => self.LCDControl = value,
             0xFF41 => self.LCDStatus = value,
@@ -184,12 +228,30 @@ impl Mem {
             SB_ADDR | SC_ADDR => self.serial.write(addr, value),
             0xFF10..=0xFF3F => self.apu.write(addr, value),
             0xFFFF => self.ienable = value,
-            0xA000..=0xBFFF => self.mbc.write(addr, value),
-            0x0000..=0x7FFF => self.mbc.write(addr, value),
+            0xA000..=0xBFFF if self.rom_lock => self.mbc.write(addr, value),
+            0x0000..=0x7FFF if self.rom_lock => self.mbc.write(addr, value),
             _ => self.mem[addr as usize] = value,
         };
     }
 
+    // STOP clears the divider from inside the CPU, no write goes out on the bus
+    pub fn reset_div(&mut self) {
+        self.timer.write(DIV_ADDR, 0);
+    }
+
+    pub fn log_accesses(&mut self, enabled: bool) {
+        self.log_accesses = enabled;
+    }
+
+    pub fn take_accesses(&mut self) -> Vec<MemAccess> {
+        self.access_log.take()
+    }
+
+    // pins what the CPU reads from LY, None goes back to the real scanline
+    pub fn stub_ly(&mut self, value: Option<u8>) {
+        self.ly_stub = value;
+    }
+
     pub fn dma_transfer(&mut self) {
         if self.transfer_count == 160 {
             self.transfer_count = 0;
@@ -236,20 +298,162 @@ impl Mem {
     }
 
     pub fn tick(&mut self) {
-        self.apu.tick();
-        let overflowed = self.timer.tick();
-        if overflowed {
-            self.mem[0xFF0F as usize] = self.mem[0xFF0F as usize] & 4;
+        self.mbc.tick();
+        let timer_interrupt = self.timer.tick();
+        self.apu.tick(self.timer.DIV);
+        if timer_interrupt {
+            self.iflag |= 0x4;
         }
     }
 
-    pub fn load_cart(&mut self, rom: Vec<u8>) {
-        let mut mbc = MBCBuilder::get_mbc_from_header(rom[0x0147], rom[0x0148], rom[0x0149]);
+    pub fn load_cart(&mut self, rom: Vec<u8>) -> Result<CartridgeInfo, EmuError> {
+        let info = CartridgeInfo::from_rom(&rom)?;
+        if !info.header_checksum_valid {
+            return Err(EmuError::ChecksumMismatch {
+                expected: info.header_checksum,
+                actual: CartridgeInfo::compute_header_checksum(&rom),
+            });
+        }
+        if rom.len() < info.rom_size as usize {
+            return Err(EmuError::TruncatedRom { expected: info.rom_size as usize, actual: rom.len() });
+        }
+        // only swap the cart in once the whole header checks out
+        let mut mbc = MBCBuilder::get_mbc_from_header(info.cartridge_type, rom[0x0148], rom[0x0149])?;
         mbc.load_cart(rom);
         self.mbc = mbc;
+        Ok(info)
+    }
+
+    // None for addresses outside the cartridge ROM
+    pub fn rom_bank(&self, addr: u16) -> Option<u16> {
+        match addr {
+            0x0000..=0x7FFF if self.rom_lock => Some(self.mbc.rom_bank(addr)),
+            _ => None
+        }
+    }
+
+    pub fn get_rtc(&mut self) -> Option<&mut Rtc> {
+        self.mbc.rtc()
+    }
+
+    pub fn get_rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    pub fn take_rumble_events(&mut self) -> Vec<bool> {
+        self.mbc.take_rumble_events()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.mbc.has_battery()
     }
 
-    pub fn get_audio_buffers(&self) -> [&[u8; 735]; 4] {
-        self.apu.get_shared_buffer()
+    pub fn export_ram(&mut self, unix_time: f64) -> Vec<u8> {
+        self.mbc.clear_ram_dirty();
+        self.mbc.save_ram(unix_time)
     }
-}
\ No newline at end of file
+
+    pub fn import_ram(&mut self, data: &[u8]) {
+        self.mbc.load_ram(data);
+        self.mbc.clear_ram_dirty();
+    }
+
+    pub fn ram_dirty(&self) -> bool {
+        self.mbc.ram_dirty()
+    }
+
+    pub fn set_audio_rate(&mut self, rate: u32) {
+        self.apu.set_output_rate(rate);
+    }
+
+    pub fn drain_audio(&mut self, out: &mut [f32]) -> usize {
+        self.apu.drain_audio(out)
+    }
+
+    pub fn start_recording(&mut self, per_channel: bool) {
+        self.apu.start_recording(per_channel);
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.apu.stop_recording()
+    }
+
+    pub fn mute_channel(&mut self, channel: usize, muted: bool) {
+        self.apu.set_muted(channel, muted);
+    }
+
+    pub fn solo_channel(&mut self, channel: usize, soloed: bool) {
+        self.apu.set_soloed(channel, soloed);
+    }
+}
+
+impl Bus for Mem {
+    fn get(&self, addr: u16) -> u8 {
+        Mem::get(self, addr)
+    }
+
+    fn set(&mut self, addr: u16, value: u8) {
+        Mem::set(self, addr, value)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Mem::peek(self, addr)
+    }
+
+    fn reset_div(&mut self) {
+        Mem::reset_div(self)
+    }
+}
+
+// the cartridge ROM is not part of a state, only the MBC registers and RAM
+impl Savable for Mem {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.mem);
+        state.u8(self.iflag);
+        state.u8(self.ienable);
+        let lcd = [
+            self.LCDControl, self.LCDStatus, self.scrolly, self.scrollx, self.ly, self.lyc,
+            self.dma, self.bgp, self.obp0, self.obp1, self.wy, self.wx
+        ];
+        state.bytes(&lcd);
+        state.bool(self.transfering);
+        state.u16(self.transfer_count);
+        self.serial.save_state(state);
+        self.joypad.save_state(state);
+        self.timer.save_state(state);
+        self.apu.save_state(state);
+        state.bool(self.ppu_access);
+        state.bool(self.rom_lock);
+        self.mbc.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        state.bytes(&mut self.mem)?;
+        self.iflag = state.u8()?;
+        self.ienable = state.u8()?;
+        let mut lcd = [0; 12];
+        state.bytes(&mut lcd)?;
+        let [lcdc, stat, scy, scx, ly, lyc, dma, bgp, obp0, obp1, wy, wx] = lcd;
+        self.LCDControl = lcdc;
+        self.LCDStatus = stat;
+        self.scrolly = scy;
+        self.scrollx = scx;
+        self.ly = ly;
+        self.lyc = lyc;
+        self.dma = dma;
+        self.bgp = bgp;
+        self.obp0 = obp0;
+        self.obp1 = obp1;
+        self.wy = wy;
+        self.wx = wx;
+        self.transfering = state.bool()?;
+        self.transfer_count = state.u16()?;
+        self.serial.load_state(state)?;
+        self.joypad.load_state(state)?;
+        self.timer.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.ppu_access = state.bool()?;
+        self.rom_lock = state.bool()?;
+        self.mbc.load_state(state)
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// the APU is mixed once per M-cycle
+const CLOCK_RATE: f64 = 1_048_576.0;
+pub const DEFAULT_OUTPUT_RATE: u32 = 44100;
+// at most a second of audio is held for the frontend
+const MAX_BUFFERED_SECONDS: usize = 1;
+
+// band-limited step kernel, taps per step and sub-sample resolution
+const KERNEL_WIDTH: usize = 16;
+const KERNEL_PHASES: usize = 32;
+// fraction of the output Nyquist frequency that is kept
+const CUTOFF: f64 = 0.9;
+
+// the DMG output capacitor leaks this much per 4 MHz clock
+const CHARGE_PER_CLOCK: f64 = 0.999958;
+
+// Every change of the mixed level is spread over KERNEL_WIDTH output samples
+// as a windowed sinc impulse, integrating the result gives a band-limited step.
+struct Resampler {
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+    step: f64,
+    time: f64,
+    level: f32,
+    sum: f32,
+    pending: VecDeque<f32>,
+}
+
+impl Resampler {
+    fn new(output_rate: u32) -> Self {
+        Self {
+            kernel: Resampler::build_kernel(),
+            step: output_rate as f64 / CLOCK_RATE,
+            time: 0.0,
+            level: 0.0,
+            sum: 0.0,
+            pending: VecDeque::from(vec![0.0; KERNEL_WIDTH]),
+        }
+    }
+
+    fn build_kernel() -> Vec<[f32; KERNEL_WIDTH]> {
+        let half = (KERNEL_WIDTH / 2) as f64;
+        (0..KERNEL_PHASES).map(|phase| {
+            let offset = phase as f64 / KERNEL_PHASES as f64;
+            let mut taps = [0.0; KERNEL_WIDTH];
+            for (k, tap) in taps.iter_mut().enumerate() {
+                let t = k as f64 - offset - half + 1.0;
+                let x = PI * CUTOFF * t;
+                let sinc = if t == 0.0 { 1.0 } else { x.sin() / x };
+                // blackman window across the kernel
+                let w = (t + half) / KERNEL_WIDTH as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                *tap = (sinc * window) as f32;
+            }
+            // each phase has to add up to exactly one step
+            let total: f32 = taps.iter().sum();
+            for tap in taps.iter_mut() {
+                *tap /= total;
+            }
+            taps
+        }).collect()
+    }
+
+    fn clock(&mut self, level: f32) {
+        let delta = level - self.level;
+        if delta != 0.0 {
+            self.level = level;
+            let index = self.time as usize;
+            let phase = ((self.time - index as f64) * KERNEL_PHASES as f64) as usize;
+            let taps = &self.kernel[phase.min(KERNEL_PHASES - 1)];
+            while self.pending.len() < index + KERNEL_WIDTH {
+                self.pending.push_back(0.0);
+            }
+            for (k, tap) in taps.iter().enumerate() {
+                self.pending[index + k] += delta * tap;
+            }
+        }
+        self.time += self.step;
+    }
+
+    // output samples that no future step can change anymore
+    fn next_sample(&mut self) -> Option<f32> {
+        if self.time < 1.0 {
+            return None;
+        }
+        self.time -= 1.0;
+        self.sum += self.pending.pop_front().unwrap_or(0.0);
+        if self.pending.len() < KERNEL_WIDTH {
+            self.pending.push_back(0.0);
+        }
+        Some(self.sum)
+    }
+}
+
+// the DAC output is AC coupled, this removes the DC offset the same way
+struct HighPass {
+    charge: f32,
+    capacitor: f32,
+}
+
+impl HighPass {
+    fn new(output_rate: u32) -> Self {
+        Self {
+            charge: CHARGE_PER_CLOCK.powf(4_194_304.0 / output_rate as f64) as f32,
+            capacitor: 0.0,
+        }
+    }
+
+    fn filter(&mut self, input: f32) -> f32 {
+        let output = input - self.capacitor;
+        self.capacitor = input - output * self.charge;
+        output
+    }
+}
+
+pub struct Mixer {
+    output_rate: u32,
+    left: Resampler,
+    right: Resampler,
+    left_filter: HighPass,
+    right_filter: HighPass,
+    // interleaved left, right
+    samples: VecDeque<f32>,
+}
+
+impl Mixer {
+    pub fn new(output_rate: u32) -> Self {
+        Self {
+            output_rate,
+            left: Resampler::new(output_rate),
+            right: Resampler::new(output_rate),
+            left_filter: HighPass::new(output_rate),
+            right_filter: HighPass::new(output_rate),
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    // `dacs` holds each channel's analog level or None when its DAC is off
+    pub fn mix(&mut self, dacs: [Option<f32>; 4], nr50: u8, nr51: u8) {
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (i, dac) in dacs.iter().enumerate() {
+            if let Some(level) = dac {
+                if nr51 >> (i + 4) & 1 == 1 {
+                    left += level;
+                }
+                if nr51 >> i & 1 == 1 {
+                    right += level;
+                }
+            }
+        }
+        let left_volume = ((nr50 >> 4 & 7) + 1) as f32 / 8.0;
+        let right_volume = ((nr50 & 7) + 1) as f32 / 8.0;
+        self.left.clock(left / 4.0 * left_volume);
+        self.right.clock(right / 4.0 * right_volume);
+
+        while let (Some(l), Some(r)) = (self.left.next_sample(), self.right.next_sample()) {
+            let left = self.left_filter.filter(l);
+            let right = self.right_filter.filter(r);
+            self.push(left, right);
+        }
+    }
+
+    fn push(&mut self, left: f32, right: f32) {
+        let limit = self.output_rate as usize * 2 * MAX_BUFFERED_SECONDS;
+        if self.samples.len() >= limit {
+            self.samples.drain(..2);
+        }
+        self.samples.push_back(left);
+        self.samples.push_back(right);
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.samples.len()
+    }
+
+    // fills whole stereo frames of `out`, returns how many floats were written
+    pub fn drain(&mut self, out: &mut [f32]) -> usize {
+        let count = self.samples.len().min(out.len() & !1);
+        for (slot, sample) in out.iter_mut().zip(self.samples.drain(..count)) {
+            *slot = sample;
+        }
+        count
+    }
+}
+
+// a single channel at full scale, for recording it on its own
+pub struct Track {
+    resampler: Resampler,
+    filter: HighPass,
+}
+
+impl Track {
+    pub fn new(output_rate: u32) -> Self {
+        Self {
+            resampler: Resampler::new(output_rate),
+            filter: HighPass::new(output_rate),
+        }
+    }
+
+    pub fn mix(&mut self, dac: Option<f32>, out: &mut Vec<f32>) {
+        self.resampler.clock(dac.unwrap_or(0.0));
+        while let Some(sample) = self.resampler.next_sample() {
+            out.push(self.filter.filter(sample));
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::time::Instant;
 use wasm_bindgen::prelude::*;
 
+use crate::error::EmuError;
 use crate::mem::Mem;
+use crate::state::{Savable, StateReader, StateWriter};
 
 const WIDTH: u8 = 160;
 const HEIGHT: u8 = 144;
@@ -13,6 +15,7 @@ const COLORS: [[u8; 4]; 4] = [
     [0x3D, 0x17, 0x52, 0xFF],
     //3d1752
 ];
+#[derive(Clone, Copy)]
 enum PPUMode {
     OAMSearch,
     PixelTransfer,
@@ -212,6 +215,50 @@ impl Ppu {
     }
 }
 
+impl Savable for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.mode as u8);
+        state.u32(self.cycles as u32);
+        for sprite in self.visible_sprites.iter() {
+            state.u16(*sprite);
+        }
+        state.u8(self.total_o as u8);
+        self.fetcher.save_state(state);
+        self.bw_fifo.save_state(state);
+        self.ob_fifo.save_state(state);
+        state.bytes(&self.display_buffer);
+        state.u8(self.x);
+        state.bool(self.wait_for_frame);
+        state.bool(self.ready);
+        state.u16(self.current_o);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.mode = match state.u8()? {
+            0 => PPUMode::OAMSearch,
+            1 => PPUMode::PixelTransfer,
+            2 => PPUMode::HBlank,
+            3 => PPUMode::VBlank,
+            _ => return Err(EmuError::CorruptState)
+        };
+        self.cycles = state.u32()? as usize;
+        for sprite in self.visible_sprites.iter_mut() {
+            *sprite = state.u16()?;
+        }
+        self.total_o = state.u8()? as usize;
+        self.fetcher.load_state(state)?;
+        self.bw_fifo.load_state(state)?;
+        self.ob_fifo.load_state(state)?;
+        state.bytes(&mut self.display_buffer)?;
+        self.x = state.u8()?;
+        self.wait_for_frame = state.bool()?;
+        self.ready = state.bool()?;
+        self.current_o = state.u16()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
 enum FetcherMode {
     ReadTile,
     Data0,
@@ -219,6 +266,7 @@ enum FetcherMode {
     Idle
 }
 
+#[derive(Clone, Copy)]
 enum BgWin {
     Background,
     Window
@@ -514,6 +562,43 @@ impl Fetcher {
     }
 }
 
+impl Savable for Fetcher {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.mode as u8);
+        state.u8(self.data0);
+        state.u8(self.data1);
+        state.u8(self.tile_num);
+        state.u16(self.backup_tile);
+        state.u16(self.curr_tile);
+        state.bool(self.bg_win_on);
+        state.u8(self.bg as u8);
+        state.u16(self.window_line);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.mode = match state.u8()? {
+            0 => FetcherMode::ReadTile,
+            1 => FetcherMode::Data0,
+            2 => FetcherMode::Data1,
+            3 => FetcherMode::Idle,
+            _ => return Err(EmuError::CorruptState)
+        };
+        self.data0 = state.u8()?;
+        self.data1 = state.u8()?;
+        self.tile_num = state.u8()?;
+        self.backup_tile = state.u16()?;
+        self.curr_tile = state.u16()?;
+        self.bg_win_on = state.bool()?;
+        self.bg = match state.u8()? {
+            0 => BgWin::Background,
+            1 => BgWin::Window,
+            _ => return Err(EmuError::CorruptState)
+        };
+        self.window_line = state.u16()?;
+        Ok(())
+    }
+}
+
 pub struct Fifo {
     data: u128,
     tail: u8,
@@ -594,6 +679,23 @@ impl Fifo {
     }
 }
 
+// is_for_sprite is fixed when the Ppu is built
+impl Savable for Fifo {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u128(self.data);
+        state.u8(self.tail);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), EmuError> {
+        self.data = state.u128()?;
+        self.tail = state.u8()?;
+        if self.tail > 16 {
+            return Err(EmuError::CorruptState);
+        }
+        Ok(())
+    }
+}
+
 trait FetcherHelpers {
     fn lcdc_bit(&self, mem: &Mem, b: u8) -> bool;
 
@@ -0,0 +1,117 @@
+use wasm_bindgen::prelude::*;
+
+use crate::mixer::{Mixer, Track};
+
+const BITS_PER_SAMPLE: u16 = 16;
+
+// Mixes its own copy of the APU output so recording never takes samples
+// away from the frontend.
+pub struct Recorder {
+    rate: u32,
+    mixer: Mixer,
+    mixed: Vec<f32>,
+    tracks: Vec<(Track, Vec<f32>)>,
+}
+
+impl Recorder {
+    pub fn new(rate: u32, per_channel: bool) -> Self {
+        let tracks = if per_channel { 4 } else { 0 };
+        Self {
+            rate,
+            mixer: Mixer::new(rate),
+            mixed: vec![],
+            tracks: (0..tracks).map(|_| (Track::new(rate), vec![])).collect(),
+        }
+    }
+
+    // `audible` is what reached the speakers, `dacs` every channel regardless of mute and solo
+    pub fn record(&mut self, audible: [Option<f32>; 4], dacs: [Option<f32>; 4], nr50: u8, nr51: u8) {
+        self.mixer.mix(audible, nr50, nr51);
+        let mut chunk = [0.0; 64];
+        loop {
+            let count = self.mixer.drain(&mut chunk);
+            if count == 0 {
+                break;
+            }
+            self.mixed.extend_from_slice(&chunk[..count]);
+        }
+
+        for ((track, samples), dac) in self.tracks.iter_mut().zip(dacs) {
+            track.mix(dac, samples);
+        }
+    }
+
+    pub fn finish(self) -> Recording {
+        Recording {
+            rate: self.rate,
+            mixed: self.mixed,
+            tracks: self.tracks.into_iter().map(|(_, samples)| samples).collect(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct Recording {
+    rate: u32,
+    // interleaved left, right
+    mixed: Vec<f32>,
+    tracks: Vec<Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl Recording {
+    pub fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    // stereo frames in the mixed output
+    pub fn len(&self) -> usize {
+        self.mixed.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mixed.is_empty()
+    }
+
+    pub fn has_channels(&self) -> bool {
+        !self.tracks.is_empty()
+    }
+
+    // the mix as a stereo 16 bit WAV
+    pub fn wav(&self) -> Vec<u8> {
+        wav(self.rate, 2, &self.mixed)
+    }
+
+    // one channel as a mono 16 bit WAV, only when it was recorded per channel
+    pub fn channel_wav(&self, channel: usize) -> Option<Vec<u8>> {
+        self.tracks.get(channel).map(|samples| wav(self.rate, 1, samples))
+    }
+}
+
+// canonical 44 byte RIFF header followed by PCM samples
+pub fn wav(rate: u32, channels: u16, samples: &[f32]) -> Vec<u8> {
+    let block_align = channels * BITS_PER_SAMPLE / 8;
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    // uncompressed PCM
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        out.extend_from_slice(&pcm.to_le_bytes());
+    }
+    out
+}
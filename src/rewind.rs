@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+// when a run of this many unchanged bytes shows up, a literal is ended
+const MIN_SKIP: usize = 4;
+
+// Keeps the newest snapshot whole, every older one is stored as a delta
+// that turns the snapshot after it back into itself. Dropping the oldest
+// entry never invalidates the others.
+pub struct Rewind {
+    interval: u32,
+    until_capture: u32,
+    budget: usize,
+    frame: u64,
+    latest: Option<(u64, Vec<u8>)>,
+    history: VecDeque<(u64, Vec<u8>)>,
+    used: usize,
+}
+
+impl Rewind {
+    pub fn new(interval: u32, budget: usize) -> Self {
+        let interval = interval.max(1);
+        Self {
+            interval,
+            until_capture: interval,
+            budget,
+            frame: 0,
+            latest: None,
+            history: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    // true when this frame should be captured
+    pub fn frame_done(&mut self) -> bool {
+        self.frame += 1;
+        self.until_capture -= 1;
+        if self.until_capture == 0 {
+            self.until_capture = self.interval;
+            return true;
+        }
+        false
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some((frame, previous)) = self.latest.take() {
+            let delta = diff(&state, &previous);
+            self.used += delta.len();
+            self.history.push_back((frame, delta));
+            self.used -= previous.len();
+        }
+        self.used += state.len();
+        self.latest = Some((self.frame, state));
+
+        while self.used > self.budget {
+            match self.history.pop_front() {
+                Some((_, delta)) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    // goes back to the newest snapshot at least `frames` ago, or the oldest one kept
+    pub fn rewind(&mut self, frames: u32) -> Option<(u32, Vec<u8>)> {
+        let target = self.frame.saturating_sub(frames as u64);
+        let (mut frame, mut state) = self.latest.take()?;
+        while frame > target {
+            match self.history.pop_back() {
+                Some((older, delta)) => {
+                    self.used -= delta.len();
+                    self.used -= state.len();
+                    state = patch(&state, &delta);
+                    self.used += state.len();
+                    frame = older;
+                },
+                None => break,
+            }
+        }
+
+        let rewound = (self.frame - frame) as u32;
+        self.frame = frame;
+        self.until_capture = self.interval;
+        self.latest = Some((frame, state.clone()));
+        Some((rewound, state))
+    }
+
+    // how many frames back the oldest snapshot is
+    pub fn available(&self) -> u32 {
+        let oldest = self.history.front().map(|(frame, _)| *frame)
+            .or_else(|| self.latest.as_ref().map(|(frame, _)| *frame));
+        match oldest {
+            Some(frame) => (self.frame - frame) as u32,
+            None => 0,
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.frame = 0;
+        self.until_capture = self.interval;
+        self.latest = None;
+        self.history.clear();
+        self.used = 0;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut v = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        v |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return v;
+        }
+        shift += 7;
+    }
+}
+
+// target length, then (skip, length, literal bytes) runs against base
+pub fn diff(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let same = |i: usize| base.get(i) == Some(&target[i]);
+    let mut out = vec![];
+    write_varint(&mut out, target.len());
+
+    let mut i = 0;
+    while i < target.len() {
+        let skip_start = i;
+        while i < target.len() && same(i) {
+            i += 1;
+        }
+        if i == target.len() {
+            break;
+        }
+
+        let start = i;
+        let mut end = i;
+        while i < target.len() {
+            if same(i) {
+                let run = (i..target.len().min(i + MIN_SKIP)).take_while(|j| same(*j)).count();
+                if run == MIN_SKIP || i + run == target.len() {
+                    break;
+                }
+                i += run;
+            } else {
+                i += 1;
+                end = i;
+            }
+        }
+
+        write_varint(&mut out, start - skip_start);
+        write_varint(&mut out, end - start);
+        out.extend_from_slice(&target[start..end]);
+        i = end;
+    }
+    out
+}
+
+pub fn patch(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut out = base.to_vec();
+    out.resize(len, 0);
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literal = read_varint(delta, &mut pos);
+        out[i..i + literal].copy_from_slice(&delta[pos..pos + literal]);
+        pos += literal;
+        i += literal;
+    }
+    out
+}
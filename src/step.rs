@@ -0,0 +1,39 @@
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::DebugCpu;
+use crate::mem::MemAccess;
+
+// what one call to Emu::step_instruction did
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct StepRecord {
+    pub pc: u16,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) mnemonic: String,
+    pub cycles: u32,
+    // the step serviced an interrupt instead of running an instruction
+    pub interrupt: bool,
+    pub before: DebugCpu,
+    pub after: DebugCpu,
+    pub(crate) accesses: Vec<MemAccess>,
+}
+
+#[wasm_bindgen]
+impl StepRecord {
+    // the opcode and its operands, empty for an interrupt or a halted CPU
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mnemonic(&self) -> String {
+        self.mnemonic.clone()
+    }
+
+    // bus reads and writes in the order the CPU made them
+    #[wasm_bindgen(getter)]
+    pub fn accesses(&self) -> Vec<MemAccess> {
+        self.accesses.clone()
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, Write};
+
+use crate::cpu::{Cpu, HalfReg};
+use crate::mem::Mem;
+
+// writes one gameboy-doctor line per instruction, taken just before it runs
+pub struct Tracer {
+    out: Box<dyn Write>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Self { out }
+    }
+
+    pub fn log(&mut self, cpu: &Cpu, mem: &Mem) -> io::Result<()> {
+        writeln!(self.out, "{}", line(cpu, mem))
+    }
+
+    pub fn into_inner(mut self) -> Box<dyn Write> {
+        let _ = self.out.flush();
+        self.out
+    }
+}
+
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub fn line(cpu: &Cpu, mem: &Mem) -> String {
+    let pc = cpu.PC;
+    let pcmem: Vec<String> = (0..4)
+        .map(|i| format!("{:02X}", mem.peek(pc.wrapping_add(i))))
+        .collect();
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{}",
+        cpu.get_byte_reg(&HalfReg::A),
+        cpu.get_byte_reg(&HalfReg::F),
+        cpu.get_byte_reg(&HalfReg::B),
+        cpu.get_byte_reg(&HalfReg::C),
+        cpu.get_byte_reg(&HalfReg::D),
+        cpu.get_byte_reg(&HalfReg::E),
+        cpu.get_byte_reg(&HalfReg::H),
+        cpu.get_byte_reg(&HalfReg::L),
+        cpu.SP,
+        pc,
+        pcmem.join(",")
+    )
+}
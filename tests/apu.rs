@@ -0,0 +1,407 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod apu_tests {
+    use dmg_emu::Emu;
+    use test_case::test_case;
+
+    // one step of the 512 Hz frame sequencer in M-cycles
+    const FRAME_STEP: usize = 2048;
+
+    fn before() -> Emu {
+        let mut emu = Emu::new();
+        emu.mem().set(0xFF26, 0x80);
+        emu
+    }
+
+    fn run(emu: &mut Emu, cycles: usize) {
+        for _ in 0..cycles {
+            emu.mem().tick();
+        }
+    }
+
+    fn outputs(emu: &mut Emu, channel: usize, cycles: usize) -> Vec<u8> {
+        let mut samples = vec![];
+        for _ in 0..cycles {
+            emu.mem().tick();
+            samples.push(emu.mem().get_apu().channel_outputs()[channel]);
+        }
+        samples
+    }
+
+    fn enabled(emu: &mut Emu, channel: usize) -> bool {
+        emu.mem().get_apu().channels_enabled()[channel]
+    }
+
+    #[test_case(0x00, 1 ;  "12.5%")]
+    #[test_case(0x40, 2 ;  "25%")]
+    #[test_case(0x80, 4 ;  "50%")]
+    #[test_case(0xC0, 6 ;  "75%")]
+    fn square_duty(nr21: u8, eighths: usize) {
+        let mut emu = before();
+        emu.mem().set(0xFF16, nr21);
+        emu.mem().set(0xFF17, 0xF0);
+        // frequency 0x700, each duty step lasts 256 M-cycles
+        emu.mem().set(0xFF18, 0x00);
+        emu.mem().set(0xFF19, 0x87);
+
+        let samples = outputs(&mut emu, 1, 256 * 8);
+        let high = samples.iter().filter(|s| **s == 15).count();
+        let low = samples.iter().filter(|s| **s == 0).count();
+        assert_eq!(high + low, samples.len());
+        assert_eq!(high, 256 * eighths);
+    }
+
+    #[test_case(0x40, false ;  "length enabled")]
+    #[test_case(0x00, true  ;  "length disabled")]
+    fn length_counter_stops_channel(length_enable: u8, still_on: bool) {
+        let mut emu = before();
+        emu.mem().set(0xFF11, 0x3F);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF14, 0x80 | length_enable);
+        assert!(enabled(&mut emu, 0));
+
+        run(&mut emu, FRAME_STEP * 3);
+        assert_eq!(enabled(&mut emu, 0), still_on);
+    }
+
+    #[test]
+    fn wave_length_is_eight_bits() {
+        let mut emu = before();
+        emu.mem().set(0xFF1A, 0x80);
+        emu.mem().set(0xFF1B, 0xFE);
+        emu.mem().set(0xFF1E, 0xC0);
+
+        // two length clocks, one every other frame sequencer step
+        run(&mut emu, FRAME_STEP * 2);
+        assert!(enabled(&mut emu, 2));
+        run(&mut emu, FRAME_STEP * 3);
+        assert!(!enabled(&mut emu, 2));
+    }
+
+    #[test]
+    fn envelope_fades_out() {
+        let mut emu = before();
+        emu.mem().set(0xFF21, 0xF1);
+        emu.mem().set(0xFF23, 0x80);
+
+        let start = outputs(&mut emu, 3, FRAME_STEP);
+        assert_eq!(start.iter().max(), Some(&15));
+
+        // 64 Hz envelope clock, 15 steps down to silence
+        run(&mut emu, FRAME_STEP * 8 * 15);
+        let end = outputs(&mut emu, 3, FRAME_STEP);
+        assert!(end.iter().all(|s| *s == 0));
+        assert!(enabled(&mut emu, 3));
+    }
+
+    #[test]
+    fn envelope_rises() {
+        let mut emu = before();
+        emu.mem().set(0xFF17, 0x19);
+        emu.mem().set(0xFF16, 0x80);
+        emu.mem().set(0xFF19, 0x87);
+
+        run(&mut emu, FRAME_STEP * 8 * 4);
+        let samples = outputs(&mut emu, 1, 256 * 8);
+        assert_eq!(samples.iter().max(), Some(&5));
+    }
+
+    #[test]
+    fn sweep_raises_frequency_then_overflows() {
+        let mut emu = before();
+        // period 1, increase, shift 1
+        emu.mem().set(0xFF10, 0x11);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF13, 0x00);
+        emu.mem().set(0xFF14, 0x84);
+
+        // the sweep is clocked on steps 2 and 6
+        // 0x400 to 0x600, the next step would overflow
+        run(&mut emu, FRAME_STEP * 3);
+        assert_eq!(emu.get_channel_status(0).unwrap().frequency, 256.0);
+        assert!(!enabled(&mut emu, 0));
+    }
+
+    #[test]
+    fn sweep_decreases_frequency() {
+        let mut emu = before();
+        emu.mem().set(0xFF10, 0x19);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF13, 0x00);
+        emu.mem().set(0xFF14, 0x84);
+
+        // 0x400 to 0x200
+        run(&mut emu, FRAME_STEP * 3);
+        assert_eq!(emu.get_channel_status(0).unwrap().frequency, 4_194_304.0 / (1536.0 * 32.0));
+        assert!(enabled(&mut emu, 0));
+    }
+
+    #[test_case(0x20, 15 ;  "full volume")]
+    #[test_case(0x40, 7  ;  "half volume")]
+    #[test_case(0x60, 3  ;  "quarter volume")]
+    #[test_case(0x00, 0  ;  "muted")]
+    fn wave_plays_ram(volume: u8, peak: u8) {
+        let mut emu = before();
+        for addr in 0xFF30..=0xFF3F {
+            emu.mem().set(addr, 0xF0);
+        }
+        emu.mem().set(0xFF1A, 0x80);
+        emu.mem().set(0xFF1C, volume);
+        // each sample lasts 128 M-cycles
+        emu.mem().set(0xFF1D, 0x00);
+        emu.mem().set(0xFF1E, 0x87);
+
+        // playback starts from the second sample after a trigger
+        run(&mut emu, 128);
+        let samples = outputs(&mut emu, 2, 128 * 32);
+        assert_eq!(samples.iter().max(), Some(&peak));
+        if peak > 0 {
+            assert_eq!(samples.iter().filter(|s| **s == peak).count(), 128 * 16);
+        }
+    }
+
+    #[test]
+    fn noise_is_random() {
+        let mut emu = before();
+        emu.mem().set(0xFF21, 0xF0);
+        emu.mem().set(0xFF22, 0x00);
+        emu.mem().set(0xFF23, 0x80);
+
+        let samples = outputs(&mut emu, 3, 1024);
+        let high = samples.iter().filter(|s| **s == 15).count();
+        assert!(high > 256 && high < 768);
+    }
+
+    #[test]
+    fn short_noise_repeats_every_127_clocks() {
+        let mut emu = before();
+        emu.mem().set(0xFF21, 0xF0);
+        // divisor 8, 7 bit mode, 2 M-cycles per LFSR clock
+        emu.mem().set(0xFF22, 0x08);
+        emu.mem().set(0xFF23, 0x80);
+
+        let samples = outputs(&mut emu, 3, 2 * 127 * 2);
+        assert_eq!(samples[..254], samples[254..]);
+        assert_ne!(samples[..127], samples[127..254]);
+    }
+
+    #[test]
+    fn dac_off_disables_channel() {
+        let mut emu = before();
+        emu.mem().set(0xFF17, 0xF0);
+        emu.mem().set(0xFF19, 0x80);
+        assert!(enabled(&mut emu, 1));
+        emu.mem().set(0xFF17, 0x00);
+        assert!(!enabled(&mut emu, 1));
+
+        // triggering without a DAC does nothing
+        emu.mem().set(0xFF19, 0x80);
+        assert!(!enabled(&mut emu, 1));
+    }
+
+    // clearing DIV while bit 4 is set counts as a falling edge
+    #[test_case(FRAME_STEP / 4, true  ;  "reset before bit 4 is set")]
+    #[test_case(FRAME_STEP / 2, false ;  "reset while bit 4 is set")]
+    fn div_reset_and_frame_sequencer(interval: usize, still_on: bool) {
+        let mut emu = before();
+        emu.mem().set(0xFF11, 0x3F);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF14, 0xC0);
+
+        for _ in 0..8 {
+            run(&mut emu, interval);
+            emu.mem().set(0xFF04, 0);
+        }
+        assert_eq!(enabled(&mut emu, 0), still_on);
+    }
+
+    #[test_case(0, 0xFF10, 512.0 ;  "square 1")]
+    #[test_case(1, 0xFF15, 512.0 ;  "square 2")]
+    #[test_case(2, 0xFF1A, 256.0 ;  "wave")]
+    fn status_frequency(channel: usize, base: u16, hz: f32) {
+        let mut emu = before();
+        emu.mem().set(base + 3, 0x00);
+        emu.mem().set(base + 4, 0x07);
+        assert_eq!(emu.get_channel_status(channel).unwrap().frequency, hz);
+    }
+
+    #[test]
+    fn status_of_square() {
+        let mut emu = before();
+        emu.mem().set(0xFF16, 0xC0);
+        emu.mem().set(0xFF17, 0xA0);
+        emu.mem().set(0xFF19, 0x80);
+
+        let status = emu.get_channel_status(1).unwrap();
+        assert!(status.enabled);
+        assert!(status.dac_enabled);
+        assert!(status.audible);
+        assert_eq!(status.volume, 10);
+        assert_eq!(status.duty, 3);
+    }
+
+    #[test_case(0x20, 15 ;  "full volume")]
+    #[test_case(0x40, 7  ;  "half volume")]
+    #[test_case(0x00, 0  ;  "muted")]
+    fn status_of_wave_volume(nr32: u8, volume: u8) {
+        let mut emu = before();
+        emu.mem().set(0xFF1C, nr32);
+        assert_eq!(emu.get_channel_status(2).unwrap().volume, volume);
+    }
+
+    #[test]
+    fn status_of_noise() {
+        let mut emu = before();
+        // divisor 16 shifted by 2, one LFSR clock every 64 T-cycles
+        emu.mem().set(0xFF22, 0x21);
+        assert_eq!(emu.get_channel_status(3).unwrap().frequency, 65536.0);
+        assert!(emu.get_channel_status(4).is_none());
+    }
+
+    #[test]
+    fn scope_holds_recent_output() {
+        let mut emu = before();
+        emu.mem().set(0xFF16, 0x80);
+        emu.mem().set(0xFF17, 0xF0);
+        emu.mem().set(0xFF18, 0x00);
+        emu.mem().set(0xFF19, 0x87);
+        run(&mut emu, 20000);
+        // about 80 scope samples of silence after the DAC goes off
+        emu.mem().set(0xFF17, 0x00);
+        run(&mut emu, 2000);
+
+        let scope = emu.get_channel_scope(1);
+        assert_eq!(scope.len(), 735);
+        assert!(scope[..600].contains(&15));
+        assert!(scope[660..].iter().all(|s| *s == 0));
+        assert!(emu.get_channel_scope(0).iter().all(|s| *s == 0));
+        assert!(emu.get_channel_scope(4).is_empty());
+    }
+
+    #[test_case(0xFF10, 0x80 ;  "NR10")]
+    #[test_case(0xFF11, 0x3F ;  "NR11")]
+    #[test_case(0xFF13, 0xFF ;  "NR13")]
+    #[test_case(0xFF14, 0xBF ;  "NR14")]
+    #[test_case(0xFF15, 0xFF ;  "unused 0xFF15")]
+    #[test_case(0xFF1A, 0x7F ;  "NR30")]
+    #[test_case(0xFF1B, 0xFF ;  "NR31")]
+    #[test_case(0xFF1C, 0x9F ;  "NR32")]
+    #[test_case(0xFF1F, 0xFF ;  "unused 0xFF1F")]
+    #[test_case(0xFF20, 0xFF ;  "NR41")]
+    #[test_case(0xFF23, 0xBF ;  "NR44")]
+    #[test_case(0xFF24, 0x00 ;  "NR50")]
+    #[test_case(0xFF2A, 0xFF ;  "unused 0xFF2A")]
+    fn unused_bits_read_as_one(addr: u16, mask: u8) {
+        let mut emu = before();
+        emu.mem().set(addr, 0x00);
+        assert_eq!(emu.mem().get(addr), mask);
+        emu.mem().set(addr, 0xFF);
+        assert_eq!(emu.mem().get(addr), 0xFF);
+    }
+
+    #[test]
+    fn nr52_reports_active_channels() {
+        let mut emu = before();
+        assert_eq!(emu.mem().get(0xFF26), 0xF0);
+        emu.mem().set(0xFF17, 0xF0);
+        emu.mem().set(0xFF19, 0x80);
+        emu.mem().set(0xFF21, 0xF0);
+        emu.mem().set(0xFF23, 0x80);
+        assert_eq!(emu.mem().get(0xFF26), 0xFA);
+        // the status bits are read only
+        emu.mem().set(0xFF26, 0x8F);
+        assert_eq!(emu.mem().get(0xFF26), 0xFA);
+    }
+
+    #[test]
+    fn power_off_clears_registers() {
+        let mut emu = before();
+        for addr in 0xFF10..=0xFF25 {
+            emu.mem().set(addr, 0xFF);
+        }
+        emu.mem().set(0xFF26, 0x00);
+        assert_eq!(emu.mem().get(0xFF26), 0x70);
+        for addr in 0xFF10..=0xFF25 {
+            let expected = match addr {
+                0xFF10 => 0x80,
+                0xFF11 | 0xFF16 => 0x3F,
+                0xFF1A => 0x7F,
+                0xFF1C => 0x9F,
+                0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => 0xBF,
+                0xFF12 | 0xFF17 | 0xFF21 | 0xFF22 | 0xFF24 | 0xFF25 => 0x00,
+                _ => 0xFF,
+            };
+            assert_eq!(emu.mem().get(addr), expected, "{:04X}", addr);
+        }
+    }
+
+    #[test]
+    fn powered_off_ignores_writes() {
+        let mut emu = before();
+        emu.mem().set(0xFF26, 0x00);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF14, 0x80);
+        emu.mem().set(0xFF24, 0x77);
+        assert_eq!(emu.mem().get(0xFF12), 0x00);
+        assert_eq!(emu.mem().get(0xFF24), 0x00);
+        assert!(!enabled(&mut emu, 0));
+
+        // still off after powering back on
+        emu.mem().set(0xFF26, 0x80);
+        assert!(!enabled(&mut emu, 0));
+        assert_eq!(emu.mem().get(0xFF12), 0x00);
+    }
+
+    // on the DMG the length counters can be loaded while the APU is off
+    #[test]
+    fn length_writable_while_powered_off() {
+        let mut emu = before();
+        emu.mem().set(0xFF26, 0x00);
+        emu.mem().set(0xFF11, 0xBF);
+        emu.mem().set(0xFF26, 0x80);
+        // the duty bits were not written
+        assert_eq!(emu.mem().get(0xFF11), 0x3F);
+
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF14, 0xC0);
+        run(&mut emu, FRAME_STEP * 2);
+        assert!(!enabled(&mut emu, 0));
+    }
+
+    #[test]
+    fn wave_ram_survives_power_off() {
+        let mut emu = before();
+        emu.mem().set(0xFF30, 0x12);
+        emu.mem().set(0xFF26, 0x00);
+        assert_eq!(emu.mem().get(0xFF30), 0x12);
+        emu.mem().set(0xFF3F, 0x34);
+        assert_eq!(emu.mem().get(0xFF3F), 0x34);
+    }
+
+    #[test]
+    fn wave_ram_while_playing() {
+        let mut emu = before();
+        for (i, addr) in (0xFF30..=0xFF3F).enumerate() {
+            emu.mem().set(addr, i as u8);
+        }
+        emu.mem().set(0xFF1A, 0x80);
+        emu.mem().set(0xFF1C, 0x20);
+        // one sample every 128 M-cycles
+        emu.mem().set(0xFF1D, 0x00);
+        emu.mem().set(0xFF1E, 0x87);
+
+        // between reads the CPU is locked out
+        run(&mut emu, 64);
+        assert_eq!(emu.mem().get(0xFF30), 0xFF);
+        emu.mem().set(0xFF35, 0xAA);
+
+        // right after a read it sees the byte being played, whatever the address
+        run(&mut emu, 64 + 128 * 4);
+        assert_eq!(emu.mem().get(0xFF3F), 2);
+
+        emu.mem().set(0xFF1A, 0x00);
+        assert_eq!(emu.mem().get(0xFF35), 5);
+    }
+}
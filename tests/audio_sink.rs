@@ -0,0 +1,115 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod audio_sink_tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use dmg_emu::audio_sink::{MemoryRing, SampleRing};
+    use test_case::test_case;
+
+    fn frames(start: usize, count: usize) -> Vec<f32> {
+        (start..start + count).flat_map(|i| vec![i as f32, -(i as f32)]).collect()
+    }
+
+    #[test]
+    fn starts_empty() {
+        let ring = MemoryRing::new(16);
+        assert_eq!(ring.available(), 0);
+        assert_eq!(ring.underruns(), 0);
+        assert_eq!(ring.overruns(), 0);
+    }
+
+    #[test]
+    fn pops_what_was_pushed() {
+        let ring = MemoryRing::new(16);
+        let samples = frames(0, 4);
+        assert_eq!(ring.push(&samples), 8);
+        assert_eq!(ring.available(), 8);
+
+        let mut out = vec![0.0; 8];
+        assert_eq!(ring.pop(&mut out), 8);
+        assert_eq!(out, samples);
+        assert_eq!(ring.available(), 0);
+        assert_eq!(ring.underruns(), 0);
+    }
+
+    #[test_case(16, 16; "exactly full")]
+    #[test_case(15, 16; "rounded to whole frames")]
+    #[test_case(2, 2; "single frame")]
+    fn holds_its_capacity(capacity: u32, held: usize) {
+        let ring = MemoryRing::new(capacity);
+        assert_eq!(ring.push(&frames(0, held / 2)), held);
+        assert_eq!(ring.overruns(), 0);
+        assert_eq!(ring.push(&frames(0, 1)), 0);
+        assert_eq!(ring.overruns(), 1);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let ring = MemoryRing::new(16);
+        let mut out = vec![0.0; 12];
+        for round in 0..10 {
+            let samples = frames(round * 6, 6);
+            assert_eq!(ring.push(&samples), 12);
+            assert_eq!(ring.pop(&mut out), 12);
+            assert_eq!(out, samples);
+        }
+        assert_eq!(ring.underruns(), 0);
+        assert_eq!(ring.overruns(), 0);
+    }
+
+    #[test]
+    fn overrun_drops_the_tail() {
+        let ring = MemoryRing::new(8);
+        assert_eq!(ring.push(&frames(0, 6)), 8);
+        assert_eq!(ring.overruns(), 1);
+
+        let mut out = vec![0.0; 8];
+        ring.pop(&mut out);
+        assert_eq!(out, frames(0, 4));
+    }
+
+    #[test]
+    fn underrun_pads_with_silence() {
+        let ring = MemoryRing::new(16);
+        ring.push(&frames(1, 2));
+
+        let mut out = vec![1.0; 8];
+        assert_eq!(ring.pop(&mut out), 4);
+        assert_eq!(out, vec![1.0, -1.0, 2.0, -2.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(ring.underruns(), 1);
+
+        assert_eq!(ring.pop(&mut out), 0);
+        assert_eq!(ring.underruns(), 2);
+    }
+
+    #[test]
+    fn keeps_order_across_threads() {
+        const TOTAL: usize = 20_000;
+        let ring = Arc::new(MemoryRing::new(256));
+
+        let producer = {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                let mut next = 0;
+                while next < TOTAL {
+                    let count = (TOTAL - next).min(37);
+                    next += ring.push(&frames(next, count)) / 2;
+                }
+            })
+        };
+
+        let mut expected = 0;
+        let mut out = vec![0.0; 50];
+        while expected < TOTAL {
+            let count = ring.pop(&mut out);
+            for frame in out[..count].chunks(2) {
+                assert_eq!(frame, [expected as f32, -(expected as f32)]);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
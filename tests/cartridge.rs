@@ -0,0 +1,155 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod cartridge_tests {
+    use dmg_emu::{Emu, cartridge::{CartridgeInfo, CgbSupport}, error::EmuError, mbc::NINTENDO_LOGO};
+    use test_case::test_case;
+
+    fn header(title: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        rom
+    }
+
+    fn sign(rom: &mut [u8]) {
+        rom[0x014D] = CartridgeInfo::compute_header_checksum(rom);
+        let global = CartridgeInfo::compute_global_checksum(rom);
+        rom[0x014E] = (global >> 8) as u8;
+        rom[0x014F] = global as u8;
+    }
+
+    #[test]
+    fn decodes_dmg_header() {
+        let mut rom = header(b"TETRIS");
+        rom.resize(128 * 1024, 0);
+        rom[0x0147] = 0x03;
+        rom[0x0148] = 0x02;
+        rom[0x0149] = 0x03;
+        rom[0x014B] = 0x01;
+        rom[0x014C] = 0x01;
+        sign(&mut rom);
+
+        let info = Emu::new().load_rom_data(rom).unwrap();
+        assert_eq!(info.title(), "TETRIS");
+        assert_eq!(info.manufacturer_code(), "");
+        assert_eq!(info.cgb, CgbSupport::None);
+        assert!(!info.sgb);
+        assert_eq!(info.cartridge_type, 0x03);
+        assert_eq!(info.rom_size, 128 * 1024);
+        assert_eq!(info.ram_size, 32 * 1024);
+        assert_eq!(info.old_licensee_code, 0x01);
+        assert_eq!(info.version, 0x01);
+        assert!(info.logo_valid);
+        assert!(info.header_checksum_valid);
+        assert!(info.global_checksum_valid);
+    }
+
+    #[test]
+    fn decodes_cgb_header() {
+        let mut rom = header(b"POKEMON_GLDAAUE\xC0");
+        rom[0x0144] = b'0';
+        rom[0x0145] = b'1';
+        rom[0x0146] = 0x03;
+        rom[0x014B] = 0x33;
+        sign(&mut rom);
+
+        let info = CartridgeInfo::from_rom(&rom).unwrap();
+        assert_eq!(info.title(), "POKEMON_GLD");
+        assert_eq!(info.manufacturer_code(), "AAUE");
+        assert_eq!(info.cgb, CgbSupport::Only);
+        assert_eq!(info.new_licensee_code(), "01");
+        assert!(info.sgb);
+    }
+
+    #[test_case(0x014D ;  "header checksum")]
+    #[test_case(0x014F ;  "global checksum")]
+    #[test_case(0x0110 ;  "logo")]
+    fn detects_corruption(addr: usize) {
+        let mut rom = header(b"CORRUPT");
+        sign(&mut rom);
+        rom[addr] ^= 0xFF;
+
+        let info = CartridgeInfo::from_rom(&rom).unwrap();
+        match addr {
+            0x014D => assert!(!info.header_checksum_valid),
+            0x014F => assert!(!info.global_checksum_valid),
+            _ => assert!(!info.logo_valid),
+        }
+    }
+
+    #[test]
+    fn remembers_loaded_cartridge() {
+        let mut emu = Emu::new();
+        assert!(emu.get_cartridge_info().is_none());
+        let mut rom = header(b"LOADED");
+        sign(&mut rom);
+        emu.load_rom_data(rom).unwrap();
+        assert_eq!(emu.get_cartridge_info().unwrap().title(), "LOADED");
+    }
+
+    #[test_case(0x20, EmuError::UnsupportedMapper(0x20) ;  "mbc6")]
+    #[test_case(0x0B, EmuError::UnsupportedMapper(0x0B) ;  "mmm01")]
+    #[test_case(0xEE, EmuError::UnsupportedMapper(0xEE) ;  "unknown type")]
+    fn rejects_unsupported_mapper(cart_type: u8, expected: EmuError) {
+        let mut rom = header(b"MAPPER");
+        rom[0x0147] = cart_type;
+        sign(&mut rom);
+        assert_eq!(Emu::new().load_rom_data(rom).err(), Some(expected));
+    }
+
+    #[test]
+    fn rejects_bad_ram_size() {
+        let mut rom = header(b"RAM");
+        rom[0x0149] = 0x09;
+        sign(&mut rom);
+        assert_eq!(Emu::new().load_rom_data(rom).err(), Some(EmuError::BadRamSize(0x09)));
+    }
+
+    #[test_case(0x0100, 0x0150 ;  "missing header")]
+    #[test_case(0x6000, 0x8000 ;  "shorter than header size")]
+    fn rejects_truncated_rom(length: usize, expected: usize) {
+        let mut rom = header(b"SHORT");
+        sign(&mut rom);
+        rom.truncate(length);
+        assert_eq!(
+            Emu::new().load_rom_data(rom).err(),
+            Some(EmuError::TruncatedRom { expected, actual: length })
+        );
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let mut rom = header(b"CHECKSUM");
+        sign(&mut rom);
+        let actual = rom[0x014D];
+        rom[0x014D] ^= 0xFF;
+        assert_eq!(
+            Emu::new().load_rom_data(rom).err(),
+            Some(EmuError::ChecksumMismatch { expected: actual ^ 0xFF, actual })
+        );
+    }
+
+    #[test]
+    fn missing_rom_file_is_an_error() {
+        match Emu::new().load_rom("./resources/no_such_rom.gb").err() {
+            Some(EmuError::UnreadableRom { path, .. }) => assert_eq!(path, "./resources/no_such_rom.gb"),
+            other => panic!("expected UnreadableRom, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_load_keeps_previous_cartridge() {
+        let mut emu = Emu::new();
+        let mut rom = header(b"FIRST");
+        sign(&mut rom);
+        emu.load_rom_data(rom).unwrap();
+
+        let mut bad = header(b"SECOND");
+        bad[0x0147] = 0xEE;
+        sign(&mut bad);
+        assert!(emu.load_rom_data(bad).is_err());
+        assert_eq!(emu.get_cartridge_info().unwrap().title(), "FIRST");
+    }
+}
@@ -7,18 +7,12 @@ extern crate test_case;
 mod cpu_assert;
 
 mod cpu_tests {
-    use dmg_emu::{Emu, cpu::{self, HalfReg, Reg}}; 
-    use crate::cpu_assert::{EmuTester, EmuTestHelpers};
+    use dmg_emu::{Emu, cpu::{self, CpuFault, HalfReg, Reg}, io::Button}; 
+    use crate::cpu_assert::{self, EmuTester, EmuTestHelpers};
     use test_case::test_case;
 
     fn before(op_codes: [u8; 3]) -> Emu {
-        let mut emu = Emu::new();
-        let mut mem: [u8; 50] = [0; 50];
-        mem[0] = op_codes[0];
-        mem[1] = op_codes[1];
-        mem[2] = op_codes[2];
-        emu.write_mem(&mem);
-        return emu
+        cpu_assert::before(&op_codes, 0)
     }
 
     #[test]
@@ -100,4 +94,252 @@ mod cpu_tests {
             ..flag(cpu::Flag::C);..equals(true);
         };
     }
-}
\ No newline at end of file
+
+    // M-cycles the next instruction takes, DIV moves 4 per M-cycle
+    fn cycles_taken(emu: &mut Emu) -> u16 {
+        let div = emu.get_timer_state().DIV;
+        emu.tick_till_done();
+        (emu.get_timer_state().DIV - div) / 4
+    }
+
+    #[test]
+    fn adc_a_r_takes_one_cycle() {
+        let mut emu = before([0x88, 0x00, 0x00]);
+        emu.cpu().set_byte_reg(&HalfReg::A, 0x0F);
+        emu.cpu().set_byte_reg(&HalfReg::B, 0x01);
+        emu.cpu().set_flag(cpu::Flag::C, true);
+        assert_eq!(cycles_taken(&mut emu), 1);
+        cascade! {
+            emu.assert();
+            ..reg(HalfReg::A);..equals(0x11u8);
+            ..flag(cpu::Flag::H);..equals(true);
+            ..reg(Reg::PC);..equals(0x1u16);
+        };
+    }
+
+    // the prefix and the second opcode byte are fetched on cycles of their own
+    #[test_case([0xCB, 0x11, 0x00], 2 ;  "RL C")]
+    #[test_case([0xCB, 0x46, 0x00], 3 ;  "BIT 0,(HL) only reads")]
+    #[test_case([0xCB, 0xFE, 0x00], 4 ;  "SET 7,(HL) writes back")]
+    fn cb_cycles(op_codes: [u8; 3], expected: u16) {
+        let mut emu = before(op_codes);
+        emu.cpu().HL = 0xC000;
+        assert_eq!(cycles_taken(&mut emu), expected);
+        assert_eq!(emu.cpu().PC, 0x2);
+    }
+
+    #[test]
+    fn bit_m_sets_zero_from_memory() {
+        let mut emu = before([0xCB, 0x7E, 0x00]);
+        emu.cpu().HL = 0xC000;
+        emu.mem().set(0xC000, 0x7F);
+        emu.tick_till_done();
+        cascade! {
+            emu.assert();
+            ..flag(cpu::Flag::Z);..equals(true);
+            ..flag(cpu::Flag::H);..equals(true);
+        };
+    }
+
+    // HALT, then INC B
+    fn halted() -> Emu {
+        let mut emu = before([0x76, 0x04, 0x00]);
+        emu.mem().set(0xFFFF, 0x04);
+        emu.mem().set(0xFF0F, 0x00);
+        emu.tick_till_done();
+        emu
+    }
+
+    #[test]
+    fn halt_waits_for_an_interrupt() {
+        let mut emu = halted();
+        emu.tick_cpu(20);
+        assert!(emu.cpu().is_halt);
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x1u16);
+            ..reg(HalfReg::B);..equals(0x00u8);
+        };
+    }
+
+    #[test]
+    fn halt_wakes_without_dispatch_when_ime_is_off() {
+        let mut emu = halted();
+        emu.cpu().SP = 0xFFFE;
+        emu.mem().set(0xFF0F, 0x04);
+        emu.tick_cpu(1);
+        assert!(!emu.cpu().is_halt);
+        emu.tick_till_done();
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x2u16);
+            ..reg(Reg::SP);..equals(0xFFFEu16);
+            ..reg(HalfReg::B);..equals(0x01u8);
+        };
+    }
+
+    #[test]
+    fn halt_wakes_into_the_handler_when_ime_is_on() {
+        let mut emu = halted();
+        emu.cpu().SP = 0xFFFE;
+        emu.cpu().ime = true;
+        emu.mem().set(0xFF0F, 0x04);
+        // one cycle to wake up, then the dispatch
+        emu.tick_cpu(2);
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x50u16);
+            ..reg(Reg::SP);..equals(0xFFFCu16);
+            ..mem(0xFFFC);..equals(0x01u8);
+        };
+    }
+
+    #[test]
+    fn halt_bug_reads_the_next_byte_twice() {
+        let mut emu = before([0x76, 0x04, 0x00]);
+        emu.mem().set(0xFFFF, 0x04);
+        emu.mem().set(0xFF0F, 0x04);
+        emu.tick_till_done();
+        assert!(!emu.cpu().is_halt);
+        emu.tick_till_done();
+        emu.tick_till_done();
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x2u16);
+            ..reg(HalfReg::B);..equals(0x02u8);
+        };
+    }
+
+    #[test]
+    fn stop_waits_for_the_joypad_and_resets_div() {
+        let mut emu = before([0x10, 0x00, 0x04]);
+        emu.mem().set(0xFF00, 0x10);
+        for _ in 0..300 {
+            emu.mem().tick();
+        }
+        emu.tick_till_done();
+        assert!(emu.cpu().is_stop);
+        assert_eq!(emu.mem().get(0xFF04), 0);
+
+        emu.tick_cpu(20);
+        assert!(emu.cpu().is_stop);
+        assert_eq!(emu.cpu().PC, 0x2);
+
+        emu.mem().button(Button::A, true);
+        emu.tick_cpu(1);
+        assert!(!emu.cpu().is_stop);
+        emu.tick_till_done();
+        cascade! {
+            emu.assert();
+            ..reg(HalfReg::B);..equals(0x01u8);
+        };
+    }
+
+    // the timer interrupt requested with IME set and the stack at `sp`
+    fn interrupted(op_codes: [u8; 3], sp: u16) -> Emu {
+        let mut emu = before(op_codes);
+        emu.cpu().SP = sp;
+        emu.cpu().ime = true;
+        emu.mem().set(0xFFFF, 0x04);
+        emu.mem().set(0xFF0F, 0x04);
+        emu
+    }
+
+    #[test]
+    fn dispatch_takes_five_cycles() {
+        let mut emu = interrupted([0x00, 0x00, 0x00], 0xFFFE);
+        let div = emu.get_timer_state().DIV;
+        emu.tick_till_done();
+        assert_eq!(emu.get_timer_state().DIV - div, 5 * 4);
+        assert!(!emu.cpu().ime);
+        assert_eq!(emu.mem().get(0xFF0F) & 0x04, 0);
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x50u16);
+            ..reg(Reg::SP);..equals(0xFFFCu16);
+            ..mem(0xFFFD);..equals(0x00u8);
+            ..mem(0xFFFC);..equals(0x00u8);
+        };
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        let mut emu = interrupted([0xFB, 0x04, 0x04], 0xFFFE);
+        emu.cpu().ime = false;
+        emu.tick_till_done();
+        emu.tick_till_done();
+        assert!(emu.cpu().ime);
+        emu.tick_till_done();
+        cascade! {
+            emu.assert();
+            ..reg(HalfReg::B);..equals(0x01u8);
+            ..reg(Reg::PC);..equals(0x50u16);
+            ..mem(0xFFFC);..equals(0x02u8);
+        };
+    }
+
+    #[test]
+    fn di_cancels_a_pending_ei() {
+        let mut emu = interrupted([0xFB, 0xF3, 0x00], 0xFFFE);
+        emu.cpu().ime = false;
+        emu.tick_cpu(3);
+        assert!(!emu.cpu().ime);
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x3u16);
+        };
+    }
+
+    // with SP at 0 the high byte of PC lands on IE
+    #[test_case(0x0100, 0x0000 ;  "push clears IE and cancels")]
+    #[test_case(0x0400, 0x0050 ;  "push keeps the timer enabled")]
+    fn ie_push(pc: u16, handler: u16) {
+        let mut emu = interrupted([0x00, 0x00, 0x00], 0x0000);
+        emu.cpu().PC = pc;
+        emu.tick_till_done();
+        assert_eq!(emu.mem().get(0xFFFF), (pc >> 8) as u8);
+        assert!(!emu.cpu().ime);
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(handler);
+            ..reg(Reg::SP);..equals(0xFFFEu16);
+        };
+    }
+
+    #[test]
+    fn if_upper_bits_read_high() {
+        let mut emu = before([0x00, 0x00, 0x00]);
+        emu.mem().set(0xFF0F, 0x00);
+        assert_eq!(emu.mem().get(0xFF0F), 0xE0);
+        emu.mem().set(0xFF0F, 0xFF);
+        assert_eq!(emu.mem().get(0xFF0F), 0xFF);
+    }
+
+    #[test_case(0xD3 ;  "0xD3")]
+    #[test_case(0xDB ;  "0xDB")]
+    #[test_case(0xDD ;  "0xDD")]
+    #[test_case(0xE3 ;  "0xE3")]
+    #[test_case(0xE4 ;  "0xE4")]
+    #[test_case(0xEB ;  "0xEB")]
+    #[test_case(0xEC ;  "0xEC")]
+    #[test_case(0xED ;  "0xED")]
+    #[test_case(0xF4 ;  "0xF4")]
+    #[test_case(0xFC ;  "0xFC")]
+    #[test_case(0xFD ;  "0xFD")]
+    fn illegal_opcode_locks_up(op: u8) {
+        let mut emu = interrupted([0x00, op, 0x04], 0xFFFE);
+        emu.cpu().ime = false;
+        emu.tick_cpu(2);
+        assert_eq!(emu.get_cpu_fault(), Some(CpuFault { pc: 0x1, opcode: op }));
+
+        // interrupts can't get it out either
+        emu.cpu().ime = true;
+        emu.tick_cpu(20);
+        assert!(!emu.tick_till_frame_done());
+        cascade! {
+            emu.assert();
+            ..reg(Reg::PC);..equals(0x2u16);
+            ..reg(HalfReg::B);..equals(0x00u8);
+        };
+    }
+}
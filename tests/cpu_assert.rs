@@ -1,6 +1,17 @@
 
 use dmg_emu::{Emu, cpu::{Flag, HalfReg, Reg}}; 
 
+// the program goes at address 0, the rest of the 50 bytes are NOPs
+#[allow(dead_code)]
+pub fn before(op_codes: &[u8], sp: u16) -> Emu {
+    let mut emu = Emu::new();
+    let mut mem: [u8; 50] = [0; 50];
+    mem[..op_codes.len()].copy_from_slice(op_codes);
+    emu.write_mem(&mem);
+    emu.cpu().SP = sp;
+    emu
+}
+
 pub trait EmuTester {
     fn assert(self) -> Assert;
 }
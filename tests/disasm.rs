@@ -0,0 +1,98 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod cpu_assert;
+mod ram_cart;
+
+mod disasm_tests {
+    use crate::{cpu_assert, ram_cart};
+    use dmg_emu::{Emu, disasm};
+    use test_case::test_case;
+
+    fn before(op_codes: &[u8], flags: u8) -> Emu {
+        let mut emu = cpu_assert::before(op_codes, 0xD000);
+        emu.cpu().AF = flags as u16;
+        emu.cpu().HL = 0xC000;
+        emu
+    }
+
+    const UNDEFINED: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+    #[test_case(&[0x00], 0x0000, "NOP", 1, (1, 1)                     ;  "nop")]
+    #[test_case(&[0x31, 0xFE, 0xFF], 0x0000, "LD SP,$FFFE", 3, (3, 3) ;  "immediate word")]
+    #[test_case(&[0x08, 0x00, 0xC0], 0x0000, "LD ($C000),SP", 3, (5, 5) ;  "store sp")]
+    #[test_case(&[0x20, 0x05], 0x0150, "JR NZ,$0157", 2, (2, 3)       ;  "relative branch")]
+    #[test_case(&[0xC4, 0x34, 0x12], 0x0000, "CALL NZ,$1234", 3, (3, 6) ;  "conditional call")]
+    #[test_case(&[0xD8], 0x0000, "RET C", 1, (2, 5)                   ;  "conditional return")]
+    #[test_case(&[0xE8, 0x80], 0x0000, "ADD SP,-128", 2, (4, 4)       ;  "signed offset")]
+    #[test_case(&[0xF0, 0x44], 0x0000, "LDH A,($FF44)", 2, (3, 3)     ;  "high page load")]
+    #[test_case(&[0xCB, 0x11], 0x0000, "RL C", 2, (2, 2)              ;  "prefixed register")]
+    #[test_case(&[0xCB, 0x46], 0x0000, "BIT 0,(HL)", 2, (3, 3)        ;  "prefixed bit test")]
+    #[test_case(&[0xCB, 0xFE], 0x0000, "SET 7,(HL)", 2, (4, 4)        ;  "prefixed write back")]
+    #[test_case(&[0xDD], 0x0000, "DB $DD", 1, (0, 0)                  ;  "undefined")]
+    fn decodes(bytes: &[u8], addr: u16, mnemonic: &str, length: u16, cycles: (u8, u8)) {
+        let instruction = disasm::decode(addr, None, bytes);
+        assert_eq!(instruction.mnemonic(), mnemonic);
+        assert_eq!(instruction.length, length);
+        assert_eq!(instruction.bytes(), bytes.to_vec());
+        assert_eq!((instruction.cycles, instruction.branch_cycles), cycles);
+    }
+
+    // the tables have to agree with what the CPU actually takes, whichever way a branch goes
+    #[test]
+    fn cycles_match_the_cpu() {
+        for opcode in 0..=0xFFu8 {
+            if UNDEFINED.contains(&opcode) || opcode == 0x10 || opcode == 0x76 {
+                continue;
+            }
+            let cb_ops: Vec<u8> = if opcode == 0xCB { (0..=0xFF).collect() } else { vec![0] };
+            for cb in cb_ops {
+                let instruction = disasm::decode(0, None, &[opcode, cb, 0xC0]);
+                let mut taken = vec![];
+                for flags in [0x00, 0xF0] {
+                    let mut emu = before(&[opcode, cb, 0xC0], flags);
+                    taken.push(emu.step_instruction().cycles as u8);
+                }
+                let expected = [instruction.cycles, instruction.branch_cycles];
+                assert!(
+                    taken.iter().all(|c| expected.contains(c)),
+                    "{} took {:?} cycles, expected {:?}", instruction.mnemonic(), taken, expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn disassembles_the_cart() {
+        let emu = ram_cart::before(b"DISASM");
+        let listing: Vec<(String, String)> = emu.disassemble(0x0150, 4)
+            .iter()
+            .map(|instruction| (instruction.location(), instruction.mnemonic()))
+            .collect();
+        assert_eq!(listing, vec![
+            ("00:0150".to_string(), "LD A,$0A".to_string()),
+            ("00:0152".to_string(), "LD ($0000),A".to_string()),
+            ("00:0155".to_string(), "LD HL,$A000".to_string()),
+            ("00:0158".to_string(), "INC (HL)".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn addresses_follow_the_mapped_bank() {
+        let mut rom = ram_cart::cart(b"DISASM");
+        rom.resize(0x10000, 0);
+        rom[0x0148] = 0x01;
+        rom[0x014D] = dmg_emu::cartridge::CartridgeInfo::compute_header_checksum(&rom);
+        rom[0xC000] = 0xC9;
+        let mut emu = Emu::new();
+        emu.load_rom_data(rom).unwrap();
+
+        emu.mem().set(0x2000, 3);
+        let instruction = &emu.disassemble(0x4000, 1)[0];
+        assert_eq!(instruction.location(), "03:4000");
+        assert_eq!(instruction.mnemonic(), "RET");
+
+        emu.mem().set(0xC000, 0x00);
+        assert_eq!(emu.disassemble(0xC000, 1)[0].location(), "C000");
+    }
+}
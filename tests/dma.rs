@@ -11,7 +11,7 @@ mod blargg_cpu_test {
 
     fn init(name: &str) -> Emu {
         let mut emu = Emu::new();
-        emu.load_rom(name);
+        emu.load_rom(format!("./resources/{}", name)).unwrap();
         emu.cpu().PC = 0x0100;
         emu.cpu().SP = 0xFFFE;
         emu.cpu().AF = 0x1180;
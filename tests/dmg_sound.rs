@@ -0,0 +1,73 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod dmg_sound_test {
+
+    use dmg_emu::Emu;
+    use test_case::test_case;
+    use std::env;
+
+    // the sound tests report through cartridge RAM instead of serial
+    const STATUS_ADDR: u16 = 0xA000;
+    const SIGNATURE_ADDR: u16 = 0xA001;
+    const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+    const TEXT_ADDR: u16 = 0xA004;
+    const RUNNING: u8 = 0x80;
+    const MAX_FRAMES: usize = 3000;
+
+    fn init(name: &str) -> Emu {
+        let mut emu = Emu::new();
+        emu.load_rom(format!("./resources/{}", name)).unwrap();
+        emu.cpu().PC = 0x0100;
+        emu.cpu().SP = 0xFFFE;
+        emu.cpu().AF = 0x01B0;
+        emu
+    }
+
+    fn text(emu: &mut Emu) -> String {
+        (TEXT_ADDR..0xC000)
+            .map(|addr| emu.mem().get(addr))
+            .take_while(|c| *c != 0)
+            .map(|c| c as char)
+            .collect()
+    }
+
+    fn run_rom(emu: &mut Emu) {
+        let mut status = None;
+        for _ in 0..MAX_FRAMES {
+            emu.tick_till_frame_done();
+            let signature: Vec<u8> = (0..3).map(|i| emu.mem().get(SIGNATURE_ADDR + i)).collect();
+            let current = emu.mem().get(STATUS_ADDR);
+            if signature == SIGNATURE && current != RUNNING {
+                status = Some(current);
+                break;
+            }
+        }
+
+        assert_eq!(status, Some(0), "{}", text(emu));
+    }
+
+    #[test_case("01-registers.gb"           ;  "01-registers.gb")]
+    #[test_case("02-len ctr.gb"             ;  "02-len ctr.gb")]
+    #[test_case("03-trigger.gb"             ;  "03-trigger.gb")]
+    #[test_case("04-sweep.gb"               ;  "04-sweep.gb")]
+    #[test_case("05-sweep details.gb"       ;  "05-sweep details.gb")]
+    #[test_case("06-overflow on trigger.gb" ;  "06-overflow on trigger.gb")]
+    #[test_case("07-len sweep period sync.gb" ;  "07-len sweep period sync.gb")]
+    #[test_case("08-len ctr during power.gb" ;  "08-len ctr during power.gb")]
+    #[test_case("09-wave read while on.gb"  ;  "09-wave read while on.gb")]
+    #[test_case("10-wave trigger while on.gb" ;  "10-wave trigger while on.gb")]
+    #[test_case("11-regs after power.gb"    ;  "11-regs after power.gb")]
+    #[test_case("12-wave write while on.gb" ;  "12-wave write while on.gb")]
+    fn dmg_sound_test(name: &str) {
+        let mut rom = name.to_string();
+        if let Ok(path) = env::var("TEST_ROM_PATH") {
+            rom = path + rom.as_str();
+        }
+
+        let mut emu = init(rom.as_str());
+        run_rom(&mut emu);
+    }
+
+}
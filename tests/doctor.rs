@@ -0,0 +1,95 @@
+use dmg_emu::Emu;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+const MAX_TICKS: usize = 50_000_000;
+
+// the tracer owns its writer, tests keep the other end of this to read the lines back
+#[derive(Clone, Default)]
+pub struct SharedLog(Rc<RefCell<Vec<u8>>>);
+
+impl SharedLog {
+    // removes and returns every finished line
+    pub fn take_lines(&self) -> Vec<String> {
+        let mut buffer = self.0.borrow_mut();
+        let end = match buffer.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => return vec![],
+        };
+        let text: Vec<u8> = buffer.drain(..end).collect();
+        String::from_utf8(text).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+impl Write for SharedLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn trace(emu: &mut Emu) -> SharedLog {
+    let log = SharedLog::default();
+    emu.set_tracer(Box::new(log.clone()));
+    log
+}
+
+pub struct Divergence {
+    // counting from 1 like the reference file
+    pub line: usize,
+    pub previous: Option<String>,
+    pub expected: String,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "trace diverged at instruction {}", self.line)?;
+        if let Some(previous) = &self.previous {
+            writeln!(f, "  after    {}", previous)?;
+        }
+        writeln!(f, "  expected {}", self.expected)?;
+        match &self.actual {
+            Some(actual) => write!(f, "  got      {}", actual),
+            None => write!(f, "  got      nothing, the CPU stopped running instructions"),
+        }
+    }
+}
+
+// runs the emulator alongside a gameboy-doctor log until the first line that differs
+pub fn first_divergence(emu: &mut Emu, reference: &str) -> Option<Divergence> {
+    let log = trace(emu);
+    let mut expected = reference.lines().filter(|line| !line.is_empty()).enumerate();
+    let mut previous = None;
+    let mut ticks = 0;
+
+    loop {
+        for actual in log.take_lines() {
+            let (index, line) = expected.next()?;
+            if actual != line {
+                return Some(Divergence { line: index + 1, previous, expected: line.to_string(), actual: Some(actual) });
+            }
+            previous = Some(actual);
+        }
+
+        if ticks == MAX_TICKS || emu.get_cpu_fault().is_some() {
+            return expected.next().map(|(index, line)| {
+                Divergence { line: index + 1, previous, expected: line.to_string(), actual: None }
+            });
+        }
+        emu.tick();
+        ticks += 1;
+    }
+}
+
+pub fn assert_trace(emu: &mut Emu, reference: &str) {
+    if let Some(divergence) = first_divergence(emu, reference) {
+        panic!("{}", divergence);
+    }
+}
@@ -0,0 +1,177 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod gbs_tests {
+    use dmg_emu::Emu;
+    use dmg_emu::error::EmuError;
+    use test_case::test_case;
+
+    const LOAD: usize = 0x0400;
+    const FRAMES: u32 = 16;
+
+    // INIT stores A at 0xC000, starts channel 2 and reads the first byte of
+    // bank 2 into 0xC002, PLAY counts its calls at 0xC001
+    fn gbs(tracks: u8, first: u8, tma: u8, tac: u8) -> Vec<u8> {
+        let mut data = vec![0; 0x70];
+        data[0..4].copy_from_slice(b"GBS\x01");
+        data[0x04] = tracks;
+        data[0x05] = first;
+        data[0x06..0x08].copy_from_slice(&(LOAD as u16).to_le_bytes());
+        data[0x08..0x0A].copy_from_slice(&0x0400u16.to_le_bytes());
+        data[0x0A..0x0C].copy_from_slice(&0x0420u16.to_le_bytes());
+        data[0x0C..0x0E].copy_from_slice(&0xDFFFu16.to_le_bytes());
+        data[0x0E] = tma;
+        data[0x0F] = tac;
+        data[0x10..0x16].copy_from_slice(b"Sample");
+        data[0x30..0x36].copy_from_slice(b"Author");
+        data[0x50..0x54].copy_from_slice(b"2024");
+
+        let mut code = vec![0; 0x8000 - LOAD + 1];
+        let init = [
+            0xEA, 0x00, 0xC0, // LD (0xC000), A
+            0x3E, 0x02,       // LD A, 2
+            0xEA, 0x00, 0x20, // LD (0x2000), A
+            0xFA, 0x00, 0x40, // LD A, (0x4000)
+            0xEA, 0x02, 0xC0, // LD (0xC002), A
+            0x3E, 0xF0,       // LD A, 0xF0
+            0xE0, 0x17,       // LDH (0x17), A
+            0x3E, 0x87,       // LD A, 0x87
+            0xE0, 0x19,       // LDH (0x19), A
+            0xC9,             // RET
+        ];
+        let play = [
+            0x21, 0x01, 0xC0, // LD HL, 0xC001
+            0x34,             // INC (HL)
+            0xC9,             // RET
+        ];
+        code[..init.len()].copy_from_slice(&init);
+        code[0x20..0x20 + play.len()].copy_from_slice(&play);
+        // first byte of bank 2
+        code[0x8000 - LOAD] = 0x5A;
+        data.extend(code);
+        data
+    }
+
+    fn before(tma: u8, tac: u8) -> Emu {
+        let mut emu = Emu::new();
+        emu.load_gbs(gbs(3, 2, tma, tac)).unwrap();
+        emu
+    }
+
+    fn run_frames(emu: &mut Emu, frames: u32) {
+        for _ in 0..frames {
+            assert!(emu.tick_till_frame_done());
+        }
+    }
+
+    #[test]
+    fn reads_metadata() {
+        let mut emu = Emu::new();
+        let info = emu.load_gbs(gbs(3, 2, 0, 0)).unwrap();
+        assert_eq!(info.title(), "Sample");
+        assert_eq!(info.author(), "Author");
+        assert_eq!(info.copyright(), "2024");
+        assert_eq!(info.track_count, 3);
+        assert_eq!(info.first_track, 1);
+        assert_eq!(info.load_addr, 0x0400);
+        assert_eq!(info.play_addr, 0x0420);
+        assert_eq!(emu.current_track(), Some(1));
+        assert!(emu.get_cartridge_info().is_none());
+    }
+
+    #[test_case(b"GBX\x01", EmuError::BadGbsHeader ;  "bad magic")]
+    #[test_case(b"GBS\x02", EmuError::BadGbsHeader ;  "unknown version")]
+    fn rejects_bad_header(magic: &[u8; 4], error: EmuError) {
+        let mut data = gbs(1, 1, 0, 0);
+        data[0..4].copy_from_slice(magic);
+        assert_eq!(Emu::new().load_gbs(data).err(), Some(error));
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        let data = gbs(1, 1, 0, 0)[..0x40].to_vec();
+        assert_eq!(Emu::new().load_gbs(data).err(), Some(EmuError::TruncatedRom { expected: 0x70, actual: 0x40 }));
+    }
+
+    #[test]
+    fn rejects_load_address_in_header() {
+        let mut data = gbs(1, 1, 0, 0);
+        data[0x06..0x08].copy_from_slice(&0x0100u16.to_le_bytes());
+        assert_eq!(Emu::new().load_gbs(data).err(), Some(EmuError::BadGbsHeader));
+    }
+
+    #[test_case(0 ;  "first")]
+    #[test_case(2 ;  "last")]
+    fn init_gets_track_in_a(track: u8) {
+        let mut emu = before(0, 0);
+        emu.select_track(track).unwrap();
+        run_frames(&mut emu, 1);
+        assert_eq!(emu.mem().get(0xC000), track);
+        assert_eq!(emu.current_track(), Some(track));
+    }
+
+    #[test]
+    fn unknown_track() {
+        let mut emu = before(0, 0);
+        assert_eq!(emu.select_track(3), Err(EmuError::NoSuchTrack(3)));
+        assert_eq!(Emu::new().select_track(0), Err(EmuError::NoSuchTrack(0)));
+    }
+
+    #[test]
+    fn banks_are_switchable() {
+        let mut emu = before(0, 0);
+        run_frames(&mut emu, 1);
+        assert_eq!(emu.mem().get(0xC002), 0x5A);
+    }
+
+    // counts the calls that finished, the one due on the last cycle has only just started
+    #[test_case(0x00, 0x00, FRAMES - 1 ;  "vblank")]
+    #[test_case(0xC0, 0x04, 17         ;  "timer 64 Hz")]
+    #[test_case(0x00, 0x06, 68         ;  "timer 256 Hz")]
+    fn play_rate(tma: u8, tac: u8, calls: u32) {
+        let mut emu = before(tma, tac);
+        run_frames(&mut emu, FRAMES);
+        assert_eq!(emu.mem().get(0xC001) as u32, calls);
+    }
+
+    #[test]
+    fn select_track_restarts() {
+        let mut emu = before(0, 0);
+        run_frames(&mut emu, 4);
+        emu.select_track(0).unwrap();
+        run_frames(&mut emu, 3);
+        assert_eq!(emu.mem().get(0xC001), 2);
+    }
+
+    #[test]
+    fn init_restarts_current_track() {
+        let mut emu = before(0, 0);
+        emu.select_track(2).unwrap();
+        run_frames(&mut emu, 4);
+        emu.init();
+        run_frames(&mut emu, 1);
+        assert_eq!(emu.mem().get(0xC000), 2);
+        assert_eq!(emu.mem().get(0xC001), 0);
+    }
+
+    #[test]
+    fn plays_audio() {
+        let mut emu = before(0, 0);
+        run_frames(&mut emu, 10);
+        assert!(emu.get_channel_status(1).unwrap().enabled);
+        let mut out = vec![0.0; 44100];
+        let count = emu.drain_audio(&mut out);
+        assert!(out[..count].iter().any(|s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn loading_a_rom_leaves_gbs_mode() {
+        let mut emu = before(0, 0);
+        let mut rom = vec![0; 0x8000];
+        rom[0x014D] = dmg_emu::cartridge::CartridgeInfo::compute_header_checksum(&rom);
+        emu.load_rom_data(rom).unwrap();
+        assert!(emu.get_gbs_info().is_none());
+        assert!(emu.current_track().is_none());
+    }
+}
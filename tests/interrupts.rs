@@ -0,0 +1,27 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod mooneye;
+
+mod mooneye_interrupt_test {
+
+    use crate::mooneye::{init, run_rom};
+    use test_case::test_case;
+
+    #[test_case("interrupts/ie_push.gb" ;  "ie_push.gb")]
+    #[test_case("ei_sequence.gb"        ;  "ei_sequence.gb")]
+    #[test_case("ei_timing.gb"          ;  "ei_timing.gb")]
+    #[test_case("di_timing-GS.gb"       ;  "di_timing-GS.gb")]
+    #[test_case("halt_ime0_ei.gb"       ;  "halt_ime0_ei.gb")]
+    #[test_case("halt_ime0_nointr_timing.gb" ;  "halt_ime0_nointr_timing.gb")]
+    #[test_case("halt_ime1_timing.gb"   ;  "halt_ime1_timing.gb")]
+    #[test_case("if_ie_registers.gb"    ;  "if_ie_registers.gb")]
+    #[test_case("intr_timing.gb"        ;  "intr_timing.gb")]
+    #[test_case("rapid_di_ei.gb"        ;  "rapid_di_ei.gb")]
+    #[test_case("reti_intr_timing.gb"   ;  "reti_intr_timing.gb")]
+    fn mooneye_interrupt_test(name: &str) {
+        let mut emu = init(name);
+        run_rom(&mut emu);
+    }
+
+}
@@ -0,0 +1,396 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod ram_cart;
+
+mod mbc_tests {
+    use crate::ram_cart;
+    use dmg_emu::{Emu, cartridge::CartridgeInfo, mbc::NINTENDO_LOGO};
+    use test_case::test_case;
+
+    // builds a cart where the first byte of every bank holds its bank number
+    fn cart(cart_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let banks = 2 << rom_size as usize;
+        let mut rom = vec![0; banks * 0x4000];
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[0x0147] = cart_type;
+        rom[0x0148] = rom_size;
+        rom[0x0149] = ram_size;
+        rom[0x014D] = CartridgeInfo::compute_header_checksum(&rom);
+        rom
+    }
+
+    fn before(rom: Vec<u8>) -> Emu {
+        let mut emu = Emu::new();
+        emu.load_rom_data(rom).unwrap();
+        emu
+    }
+
+    #[test_case(0x00, 1    ;  "bank 0 maps to bank 1")]
+    #[test_case(0x01, 1    ;  "bank 1")]
+    #[test_case(0x1F, 31   ;  "bank 31")]
+    #[test_case(0x20, 1    ;  "only 5 bits are used")]
+    #[test_case(0x45, 5    ;  "upper bits are masked")]
+    fn mbc1_rom_bank(value: u8, expected: u8) {
+        let mut emu = before(cart(0x01, 0x05, 0x00));
+        emu.mem().set(0x2000, value);
+        assert_eq!(emu.mem().get(0x4000), expected);
+    }
+
+    #[test]
+    fn mbc1_rom_bank_wraps_to_rom_size() {
+        let mut emu = before(cart(0x01, 0x02, 0x00));
+        emu.mem().set(0x2000, 0x09);
+        assert_eq!(emu.mem().get(0x4000), 1);
+    }
+
+    #[test]
+    fn mbc1_bank2_selects_upper_rom_bits() {
+        let mut emu = before(cart(0x01, 0x06, 0x00));
+        emu.mem().set(0x2000, 0x02);
+        emu.mem().set(0x4000, 0x03);
+        assert_eq!(emu.mem().get(0x4000), 0x62);
+        // bank 0 is fixed in simple mode
+        assert_eq!(emu.mem().get(0x0000), 0x00);
+
+        emu.mem().set(0x6000, 0x01);
+        assert_eq!(emu.mem().get(0x0000), 0x60);
+    }
+
+    #[test]
+    fn mbc1_ram_needs_enabling() {
+        let mut emu = before(cart(0x03, 0x01, 0x02));
+        emu.mem().set(0xA000, 0x42);
+        assert_eq!(emu.mem().get(0xA000), 0xFF);
+
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0xA000, 0x42);
+        assert_eq!(emu.mem().get(0xA000), 0x42);
+
+        emu.mem().set(0x0000, 0x00);
+        assert_eq!(emu.mem().get(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc1_ram_banking_needs_advanced_mode() {
+        let mut emu = before(cart(0x03, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0xA000, 0x11);
+
+        emu.mem().set(0x4000, 0x02);
+        assert_eq!(emu.mem().get(0xA000), 0x11);
+
+        emu.mem().set(0x6000, 0x01);
+        emu.mem().set(0xA000, 0x22);
+        assert_eq!(emu.mem().get(0xA000), 0x22);
+
+        emu.mem().set(0x4000, 0x00);
+        assert_eq!(emu.mem().get(0xA000), 0x11);
+    }
+
+    #[test]
+    fn mbc1_multicart_uses_4_bit_bank1() {
+        let mut rom = cart(0x01, 0x05, 0x00);
+        rom[0x40104..0x40104 + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut emu = before(rom);
+
+        emu.mem().set(0x2000, 0x12);
+        emu.mem().set(0x4000, 0x01);
+        assert_eq!(emu.mem().get(0x4000), 0x12);
+
+        emu.mem().set(0x6000, 0x01);
+        assert_eq!(emu.mem().get(0x0000), 0x10);
+    }
+
+    fn latch_rtc(emu: &mut Emu) {
+        emu.mem().set(0x6000, 0x00);
+        emu.mem().set(0x6000, 0x01);
+    }
+
+    fn read_rtc(emu: &mut Emu, reg: u8) -> u8 {
+        emu.mem().set(0x4000, reg);
+        emu.mem().get(0xA000)
+    }
+
+    #[test_case(0x00, 1    ;  "bank 0 maps to bank 1")]
+    #[test_case(0x1F, 31   ;  "bank 31")]
+    #[test_case(0x7F, 127  ;  "bank 127")]
+    #[test_case(0xC5, 69   ;  "only 7 bits are used")]
+    fn mbc3_rom_bank(value: u8, expected: u8) {
+        let mut emu = before(cart(0x13, 0x06, 0x03));
+        emu.mem().set(0x2000, value);
+        assert_eq!(emu.mem().get(0x4000), expected);
+    }
+
+    #[test]
+    fn mbc3_ram_banking() {
+        let mut emu = before(cart(0x13, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x00);
+        emu.mem().set(0xA000, 0x11);
+        emu.mem().set(0x4000, 0x03);
+        emu.mem().set(0xA000, 0x33);
+
+        emu.mem().set(0x4000, 0x00);
+        assert_eq!(emu.mem().get(0xA000), 0x11);
+        emu.mem().set(0x4000, 0x03);
+        assert_eq!(emu.mem().get(0xA000), 0x33);
+    }
+
+    #[test]
+    fn mbc3_rtc_only_changes_on_latch() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        for _ in 0..1_048_576 * 2 {
+            emu.mem().tick();
+        }
+        assert_eq!(read_rtc(&mut emu, 0x08), 0);
+
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 2);
+    }
+
+    #[test]
+    fn mbc3_rtc_carries_into_days() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x0A);
+        emu.mem().set(0xA000, 23);
+        emu.mem().set(0x4000, 0x09);
+        emu.mem().set(0xA000, 59);
+        emu.mem().set(0x4000, 0x08);
+        emu.mem().set(0xA000, 59);
+        emu.mem().set(0x4000, 0x0B);
+        emu.mem().set(0xA000, 0xFF);
+        emu.mem().set(0x4000, 0x0C);
+        emu.mem().set(0xA000, 0x01);
+
+        for _ in 0..1_048_576 {
+            emu.mem().tick();
+        }
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 0);
+        assert_eq!(read_rtc(&mut emu, 0x09), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0A), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0B), 0);
+        // day counter overflowed
+        assert_eq!(read_rtc(&mut emu, 0x0C), 0x80);
+    }
+
+    #[test]
+    fn mbc3_rtc_halt_stops_the_clock() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x0C);
+        emu.mem().set(0xA000, 0x40);
+        for _ in 0..1_048_576 {
+            emu.mem().tick();
+        }
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0C), 0x40);
+    }
+
+    #[test]
+    fn mbc3_rtc_follows_host_clock() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.use_host_clock(true);
+        emu.sync_rtc(1_000_000.0);
+        emu.sync_rtc(1_000_000.0 + 86400.0 + 3600.0 + 61.5);
+
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 1);
+        assert_eq!(read_rtc(&mut emu, 0x09), 1);
+        assert_eq!(read_rtc(&mut emu, 0x0A), 1);
+        assert_eq!(read_rtc(&mut emu, 0x0B), 1);
+    }
+
+    #[test_case(0x00, 0x00, 0     ;  "bank 0 is not remapped")]
+    #[test_case(0x01, 0x00, 1     ;  "bank 1")]
+    #[test_case(0xFF, 0x00, 255   ;  "bank 255")]
+    #[test_case(0x00, 0x01, 256   ;  "ninth bit selects bank 256")]
+    #[test_case(0xFF, 0x01, 511   ;  "bank 511")]
+    fn mbc5_rom_bank(low: u8, high: u8, expected: u16) {
+        let mut rom = cart(0x19, 0x08, 0x00);
+        rom[256 * 0x4000 + 1] = 1;
+        rom[511 * 0x4000 + 1] = 1;
+        let mut emu = before(rom);
+        emu.mem().set(0x2000, low);
+        emu.mem().set(0x3000, high);
+        let bank = (emu.mem().get(0x4001) as u16) << 8 | emu.mem().get(0x4000) as u16;
+        assert_eq!(bank, expected);
+    }
+
+    #[test]
+    fn mbc5_ram_banking() {
+        let mut emu = before(cart(0x1B, 0x01, 0x04));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x0F);
+        emu.mem().set(0xA000, 0x0F);
+        emu.mem().set(0x4000, 0x00);
+        emu.mem().set(0xA000, 0x01);
+
+        emu.mem().set(0x4000, 0x0F);
+        assert_eq!(emu.mem().get(0xA000), 0x0F);
+        emu.mem().set(0x4000, 0x00);
+        assert_eq!(emu.mem().get(0xA000), 0x01);
+    }
+
+    #[test]
+    fn mbc5_rumble_events() {
+        let mut emu = before(cart(0x1E, 0x01, 0x03));
+        emu.mem().set(0x4000, 0x08);
+        assert!(emu.is_rumbling());
+        emu.mem().set(0x4000, 0x09);
+        emu.mem().set(0x4000, 0x01);
+        assert!(!emu.is_rumbling());
+
+        assert_eq!(emu.take_rumble_events(), vec![1, 0]);
+        assert!(emu.take_rumble_events().is_empty());
+    }
+
+    #[test]
+    fn mbc5_rumble_bit_does_not_select_ram() {
+        let mut emu = before(cart(0x1E, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x01);
+        emu.mem().set(0xA000, 0x42);
+        emu.mem().set(0x4000, 0x09);
+        assert_eq!(emu.mem().get(0xA000), 0x42);
+    }
+
+    #[test_case(0x2100, 0x00, 1    ;  "bank 0 maps to bank 1")]
+    #[test_case(0x2100, 0x0F, 15   ;  "bank 15")]
+    #[test_case(0x2100, 0x13, 3    ;  "only 4 bits are used")]
+    #[test_case(0x0100, 0x05, 5    ;  "bit 8 selects the rom bank register")]
+    #[test_case(0x2000, 0x05, 1    ;  "bit 8 clear is the ram enable register")]
+    fn mbc2_rom_bank(addr: u16, value: u8, expected: u8) {
+        let mut emu = before(cart(0x05, 0x03, 0x00));
+        emu.mem().set(addr, value);
+        assert_eq!(emu.mem().get(0x4000), expected);
+    }
+
+    #[test]
+    fn mbc2_ram_is_4_bits() {
+        let mut emu = before(cart(0x06, 0x01, 0x00));
+        emu.mem().set(0xA000, 0x0A);
+        assert_eq!(emu.mem().get(0xA000), 0xFF);
+
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0xA000, 0x5A);
+        assert_eq!(emu.mem().get(0xA000), 0xFA);
+    }
+
+    #[test]
+    fn mbc2_ram_echoes() {
+        let mut emu = before(cart(0x06, 0x01, 0x00));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0xA1FF, 0x03);
+        assert_eq!(emu.mem().get(0xA3FF), 0xF3);
+        assert_eq!(emu.mem().get(0xBFFF), 0xF3);
+    }
+
+    #[test]
+    fn save_ram_round_trip() {
+        let mut emu = before(cart(0x03, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x6000, 0x01);
+        emu.mem().set(0x4000, 0x02);
+        emu.mem().set(0xA123, 0x42);
+        assert!(emu.is_save_dirty());
+
+        let save = emu.export_save(1_600_000_000.0);
+        assert_eq!(save.len(), 32 * 1024);
+        assert_eq!(save[2 * 0x2000 + 0x123], 0x42);
+        assert!(!emu.is_save_dirty());
+
+        let mut emu = before(cart(0x03, 0x01, 0x03));
+        emu.import_save(save);
+        assert!(!emu.is_save_dirty());
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x6000, 0x01);
+        emu.mem().set(0x4000, 0x02);
+        assert_eq!(emu.mem().get(0xA123), 0x42);
+    }
+
+    // the frontend flushes once the game has written its RAM
+    #[test]
+    fn running_game_dirties_the_save() {
+        let mut emu = ram_cart::before(b"SAVE");
+        assert!(!emu.is_save_dirty());
+        ram_cart::run_frames(&mut emu, 2);
+        assert!(emu.is_save_dirty());
+
+        let save = emu.export_save(0.0);
+        assert!(!emu.is_save_dirty());
+        assert_eq!(save.len(), 8 * 1024);
+        assert_ne!(save[0], 0);
+    }
+
+    #[test_case(0x03, 0x02, true   ;  "mbc1 with battery")]
+    #[test_case(0x02, 0x02, false  ;  "mbc1 without battery")]
+    #[test_case(0x06, 0x00, true   ;  "mbc2 with battery")]
+    #[test_case(0x1B, 0x02, true   ;  "mbc5 with battery")]
+    fn has_battery(cart_type: u8, ram_size: u8, expected: bool) {
+        let emu = before(cart(cart_type, 0x01, ram_size));
+        assert_eq!(emu.has_battery(), expected);
+    }
+
+    #[test]
+    fn mbc2_save_is_512_bytes() {
+        let mut emu = before(cart(0x06, 0x01, 0x00));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0xA010, 0x07);
+        let save = emu.export_save(1_600_000_000.0);
+        assert_eq!(save.len(), 512);
+        assert_eq!(save[0x10], 0x07);
+    }
+
+    #[test]
+    fn mbc3_save_has_rtc_trailer() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x09);
+        emu.mem().set(0xA000, 42);
+        emu.use_host_clock(true);
+        emu.sync_rtc(1_600_000_000.0);
+
+        let save = emu.export_save(1_600_000_000.0);
+        assert_eq!(save.len(), 32 * 1024 + 48);
+        let trailer = &save[32 * 1024..];
+        assert_eq!(trailer[4], 42);
+        assert_eq!(&trailer[40..48], &1_600_000_000u64.to_le_bytes());
+
+        // time passes between sessions
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.import_save(save);
+        emu.use_host_clock(true);
+        emu.sync_rtc(1_600_000_000.0 + 120.0);
+        emu.mem().set(0x0000, 0x0A);
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x09), 44);
+    }
+
+    // the cycle driven clock never syncs, the trailer still carries the time of the save
+    #[test]
+    fn mbc3_save_stamps_the_time_of_the_save() {
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.mem().set(0x0000, 0x0A);
+        emu.mem().set(0x4000, 0x0A);
+        emu.mem().set(0xA000, 5);
+
+        let save = emu.export_save(1_700_000_000.0);
+        assert_eq!(&save[32 * 1024 + 40..], &1_700_000_000u64.to_le_bytes());
+
+        let mut emu = before(cart(0x10, 0x01, 0x03));
+        emu.import_save(save);
+        emu.use_host_clock(true);
+        emu.sync_rtc(1_700_000_000.0 + 7200.0);
+        emu.mem().set(0x0000, 0x0A);
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x0A), 7);
+    }
+}
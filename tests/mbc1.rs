@@ -0,0 +1,29 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod mooneye;
+
+mod mooneye_mbc1_test {
+
+    use crate::mooneye::{init, run_rom};
+    use test_case::test_case;
+
+    #[test_case("bits_bank1.gb"         ;  "bits_bank1.gb")]
+    #[test_case("bits_bank2.gb"         ;  "bits_bank2.gb")]
+    #[test_case("bits_mode.gb"          ;  "bits_mode.gb")]
+    #[test_case("bits_ramg.gb"          ;  "bits_ramg.gb")]
+    #[test_case("ram_64kb.gb"           ;  "ram_64kb.gb")]
+    #[test_case("ram_256kb.gb"          ;  "ram_256kb.gb")]
+    #[test_case("rom_512kb.gb"          ;  "rom_512kb.gb")]
+    #[test_case("rom_1Mb.gb"            ;  "rom_1Mb.gb")]
+    #[test_case("rom_2Mb.gb"            ;  "rom_2Mb.gb")]
+    #[test_case("rom_4Mb.gb"            ;  "rom_4Mb.gb")]
+    #[test_case("rom_8Mb.gb"            ;  "rom_8Mb.gb")]
+    #[test_case("rom_16Mb.gb"           ;  "rom_16Mb.gb")]
+    #[test_case("multicart_rom_8Mb.gb"  ;  "multicart_rom_8Mb.gb")]
+    fn mooneye_mbc1_test(name: &str) {
+        let mut emu = init(name);
+        run_rom(&mut emu);
+    }
+
+}
@@ -0,0 +1,159 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod mixer_tests {
+    use dmg_emu::Emu;
+    use test_case::test_case;
+
+    const RATE: u32 = 48000;
+    // a tenth of a second of M-cycles
+    const TENTH: usize = 104_858;
+
+    fn before(nr50: u8, nr51: u8) -> Emu {
+        let mut emu = Emu::new();
+        emu.set_audio_rate(RATE);
+        emu.mem().set(0xFF26, 0x80);
+        emu.mem().set(0xFF24, nr50);
+        emu.mem().set(0xFF25, nr51);
+        emu
+    }
+
+    // channel 2 square wave at 50% duty
+    fn play_square(emu: &mut Emu, frequency: u16) {
+        emu.mem().set(0xFF16, 0x80);
+        emu.mem().set(0xFF17, 0xF0);
+        emu.mem().set(0xFF18, frequency as u8);
+        emu.mem().set(0xFF19, 0x80 | (frequency >> 8) as u8);
+    }
+
+    fn run(emu: &mut Emu, cycles: usize) -> Vec<f32> {
+        for _ in 0..cycles {
+            emu.mem().tick();
+        }
+        let mut out = vec![0.0; RATE as usize * 2];
+        let count = emu.drain_audio(&mut out);
+        out.truncate(count);
+        out
+    }
+
+    fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+        let (sum, count) = samples.fold((0.0, 0), |(sum, count), s| (sum + s * s, count + 1));
+        (sum / count as f32).sqrt()
+    }
+
+    fn left(samples: &[f32]) -> impl Iterator<Item = f32> + '_ {
+        samples.iter().step_by(2).copied()
+    }
+
+    fn right(samples: &[f32]) -> impl Iterator<Item = f32> + '_ {
+        samples.iter().skip(1).step_by(2).copied()
+    }
+
+    #[test_case(48000 ;  "48 kHz")]
+    #[test_case(44100 ;  "44.1 kHz")]
+    #[test_case(22050 ;  "22.05 kHz")]
+    fn produces_output_rate(rate: u32) {
+        let mut emu = before(0x77, 0xFF);
+        emu.set_audio_rate(rate);
+        let samples = run(&mut emu, TENTH);
+        let frames = samples.len() / 2;
+        assert!((frames as i64 - rate as i64 / 10).abs() <= 1);
+    }
+
+    #[test]
+    fn drains_whole_frames() {
+        let mut emu = before(0x77, 0xFF);
+        for _ in 0..1000 {
+            emu.mem().tick();
+        }
+        let mut out = [0.0; 7];
+        assert_eq!(emu.drain_audio(&mut out), 6);
+    }
+
+    #[test_case(0x20, true, false  ;  "left only")]
+    #[test_case(0x02, false, true  ;  "right only")]
+    #[test_case(0x22, true, true   ;  "center")]
+    #[test_case(0x11, false, false ;  "other channel")]
+    fn pans_with_nr51(nr51: u8, on_left: bool, on_right: bool) {
+        let mut emu = before(0x77, nr51);
+        play_square(&mut emu, 0x700);
+        let samples = run(&mut emu, TENTH);
+        assert_eq!(rms(left(&samples)) > 0.1, on_left);
+        assert_eq!(rms(right(&samples)) > 0.1, on_right);
+    }
+
+    #[test]
+    fn scales_with_nr50() {
+        let mut loud = before(0x77, 0xFF);
+        let mut quiet = before(0x00, 0xFF);
+        play_square(&mut loud, 0x700);
+        play_square(&mut quiet, 0x700);
+        let loud = rms(left(&run(&mut loud, TENTH)));
+        let quiet = rms(left(&run(&mut quiet, TENTH)));
+        assert!((loud / quiet - 8.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn removes_dc_offset() {
+        // the DAC is on but the volume is 0, so it sits at a constant level
+        let mut emu = before(0x77, 0xFF);
+        emu.mem().set(0xFF17, 0x08);
+        emu.mem().set(0xFF19, 0x80);
+        let samples = run(&mut emu, TENTH * 5);
+        let tail = &samples[samples.len() - 200..];
+        assert!(tail.iter().all(|s| s.abs() < 0.001));
+    }
+
+    #[test]
+    fn filters_ultrasonic_tones() {
+        // 131 kHz is far above what 48 kHz can carry and must not alias down
+        let mut emu = before(0x77, 0xFF);
+        play_square(&mut emu, 2047);
+        let samples = run(&mut emu, TENTH);
+        assert!(rms(left(&samples[2000..])) < 0.01);
+    }
+
+    #[test]
+    fn output_stays_in_range() {
+        let mut emu = before(0x77, 0xFF);
+        play_square(&mut emu, 0x600);
+        emu.mem().set(0xFF12, 0xF0);
+        emu.mem().set(0xFF14, 0x87);
+        let samples = run(&mut emu, TENTH);
+        assert!(samples.iter().all(|s| s.abs() <= 1.0));
+    }
+
+    // only channel 2 is playing
+    #[test_case(&[1], &[], false  ;  "muted")]
+    #[test_case(&[0], &[], true   ;  "other channel muted")]
+    #[test_case(&[], &[1], true   ;  "soloed")]
+    #[test_case(&[], &[0], false  ;  "other channel soloed")]
+    #[test_case(&[1], &[1], false ;  "muted and soloed")]
+    fn mute_and_solo(muted: &[usize], soloed: &[usize], audible: bool) {
+        let mut emu = before(0x77, 0xFF);
+        for channel in muted {
+            emu.mute_channel(*channel, true);
+        }
+        for channel in soloed {
+            emu.solo_channel(*channel, true);
+        }
+        play_square(&mut emu, 0x700);
+        let samples = run(&mut emu, TENTH);
+        assert_eq!(rms(left(&samples)) > 0.1, audible);
+        assert_eq!(emu.get_channel_status(1).unwrap().audible, audible);
+    }
+
+    #[test]
+    fn unmuting_restores_output() {
+        let mut emu = before(0x77, 0xFF);
+        emu.mute_channel(1, true);
+        play_square(&mut emu, 0x700);
+        run(&mut emu, TENTH);
+        emu.mute_channel(1, false);
+        let samples = run(&mut emu, TENTH);
+        assert!(rms(left(&samples)) > 0.1);
+        // the channel kept running while it was muted
+        assert!(emu.get_channel_status(1).unwrap().enabled);
+    }
+}
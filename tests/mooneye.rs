@@ -0,0 +1,44 @@
+use dmg_emu::{Emu, cpu::HalfReg};
+use std::env;
+
+// mooneye roms signal the end of a test by executing LD B, B
+const LD_B_B: u8 = 0x40;
+const MAX_INSTRUCTIONS: usize = 50_000_000;
+
+pub fn init(name: &str) -> Emu {
+    let mut rom = name.to_string();
+    if let Ok(path) = env::var("TEST_ROM_PATH") {
+        rom = path + rom.as_str();
+    }
+
+    let mut emu = Emu::new();
+    emu.load_rom(format!("./resources/{}", rom)).unwrap();
+    emu.cpu().PC = 0x0100;
+    emu.cpu().SP = 0xFFFE;
+    emu.cpu().AF = 0x01B0;
+    emu
+}
+
+pub fn run_rom(emu: &mut Emu) {
+    let mut finished = false;
+    for _ in 0..MAX_INSTRUCTIONS {
+        emu.tick();
+        if emu.cpu().current_op == LD_B_B {
+            finished = true;
+            break;
+        }
+    }
+    assert!(finished, "test rom never finished");
+
+    // a passing test loads the fibonacci numbers into the registers
+    let cpu = emu.cpu();
+    let registers = [
+        cpu.get_byte_reg(&HalfReg::B),
+        cpu.get_byte_reg(&HalfReg::C),
+        cpu.get_byte_reg(&HalfReg::D),
+        cpu.get_byte_reg(&HalfReg::E),
+        cpu.get_byte_reg(&HalfReg::H),
+        cpu.get_byte_reg(&HalfReg::L),
+    ];
+    assert_eq!(registers, [3, 5, 8, 13, 21, 34]);
+}
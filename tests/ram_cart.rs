@@ -0,0 +1,38 @@
+use dmg_emu::{Emu, cartridge::CartridgeInfo};
+
+// MBC1 cart that enables RAM then keeps incrementing every byte of it
+pub fn cart(title: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+    rom[0x0147] = 0x03;
+    rom[0x0149] = 0x02;
+    rom[0x014D] = CartridgeInfo::compute_header_checksum(&rom);
+    let program = [
+        0x3E, 0x0A,       // LD A, 0x0A
+        0xEA, 0x00, 0x00, // LD (0x0000), A
+        0x21, 0x00, 0xA0, // LD HL, 0xA000
+        0x34,             // INC (HL)
+        0x23,             // INC HL
+        0x7C,             // LD A, H
+        0xFE, 0xC0,       // CP 0xC0
+        0x20, 0xF9,       // JR NZ, -7
+        0xC3, 0x55, 0x01, // JP 0x0155
+    ];
+    rom[0x0150..0x0150 + program.len()].copy_from_slice(&program);
+    rom
+}
+
+pub fn before(title: &[u8]) -> Emu {
+    let mut emu = Emu::new();
+    emu.load_rom_data(cart(title)).unwrap();
+    emu.init();
+    emu
+}
+
+#[allow(dead_code)]
+pub fn run_frames(emu: &mut Emu, frames: usize) {
+    for _ in 0..frames {
+        emu.tick_till_frame_done();
+    }
+}
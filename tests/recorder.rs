@@ -0,0 +1,149 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+mod recorder_tests {
+    use std::{env, fs};
+
+    use dmg_emu::Emu;
+    use dmg_emu::cartridge::CartridgeInfo;
+    use dmg_emu::recorder::{wav, Recording};
+    use test_case::test_case;
+
+    const RATE: u32 = 22050;
+    const GOLDEN: &str = "tests/golden/square_noise.wav";
+
+    // starts a fading square on channel 2 and noise on channel 4, then spins
+    fn cart() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x0134..0x0139].copy_from_slice(b"SOUND");
+        rom[0x014D] = CartridgeInfo::compute_header_checksum(&rom);
+        let writes: [(u8, u8); 10] = [
+            (0x26, 0x80), (0x24, 0x77), (0x25, 0xFF),
+            (0x16, 0x80), (0x17, 0xF3), (0x18, 0x00), (0x19, 0x87),
+            (0x21, 0xA1), (0x22, 0x35), (0x23, 0x80),
+        ];
+        let mut program = vec![];
+        for (reg, value) in writes {
+            // LD A, value ; LDH (reg), A
+            program.extend_from_slice(&[0x3E, value, 0xE0, reg]);
+        }
+        // JR -2
+        program.extend_from_slice(&[0x18, 0xFE]);
+        rom[0x0150..0x0150 + program.len()].copy_from_slice(&program);
+        rom
+    }
+
+    fn before() -> Emu {
+        let mut emu = Emu::new();
+        emu.load_rom_data(cart()).unwrap();
+        emu.init();
+        emu.set_audio_rate(RATE);
+        emu
+    }
+
+    fn pcm(wav: &[u8]) -> Vec<i16> {
+        wav[44..].chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]])).collect()
+    }
+
+    fn u32_at(data: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+    }
+
+    fn peak(wav: &[u8]) -> i16 {
+        pcm(wav).iter().map(|s| s.saturating_abs()).max().unwrap_or(0)
+    }
+
+    // BLESS_GOLDEN=1 rewrites the golden file from the current output
+    #[test]
+    fn matches_golden_file() {
+        let recording = before().record_audio(10, false);
+        let actual = recording.wav();
+        if env::var("BLESS_GOLDEN").is_ok() {
+            fs::write(GOLDEN, &actual).unwrap();
+        }
+
+        let golden = fs::read(GOLDEN).unwrap();
+        assert_eq!(actual.len(), golden.len());
+        assert_eq!(actual[..44], golden[..44]);
+        // float rounding may differ by one step between platforms
+        for (i, (a, g)) in pcm(&actual).iter().zip(pcm(&golden)).enumerate() {
+            assert!((*a as i32 - g as i32).abs() <= 1, "sample {} is {}, expected {}", i, a, g);
+        }
+    }
+
+    #[test]
+    fn writes_wav_header() {
+        let out = wav(RATE, 2, &[0.0, 1.0, -1.0, 0.5]);
+        assert_eq!(&out[0..4], b"RIFF");
+        assert_eq!(u32_at(&out, 4), 36 + 8);
+        assert_eq!(&out[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([out[22], out[23]]), 2);
+        assert_eq!(u32_at(&out, 24), RATE);
+        assert_eq!(u32_at(&out, 28), RATE * 4);
+        assert_eq!(u16::from_le_bytes([out[34], out[35]]), 16);
+        assert_eq!(&out[36..40], b"data");
+        assert_eq!(pcm(&out), vec![0, 32767, -32767, 16384]);
+    }
+
+    // the first frame only runs from power on to the first vblank
+    #[test_case(3 ;  "three frames")]
+    #[test_case(30 ;  "thirty frames")]
+    fn records_every_frame(frames: u32) {
+        let first = before().record_audio(1, false).len();
+        let frame = before().record_audio(2, false).len() - first;
+        let recording = before().record_audio(frames, false);
+        let expected = first + frame * (frames as usize - 1);
+        assert!((recording.len() as i64 - expected as i64).abs() <= 16);
+        assert_eq!(recording.sample_rate(), RATE);
+    }
+
+    #[test]
+    fn records_channels_separately() {
+        let recording = before().record_audio(10, true);
+        assert!(recording.has_channels());
+        let frames = recording.len();
+        let tracks: Vec<Vec<u8>> = (0..4).map(|c| recording.channel_wav(c).unwrap()).collect();
+        for track in &tracks {
+            assert_eq!(u16::from_le_bytes([track[22], track[23]]), 1);
+            assert_eq!(pcm(track).len(), frames);
+        }
+        assert!(peak(&tracks[1]) > 1000);
+        assert!(peak(&tracks[3]) > 1000);
+        assert_eq!(peak(&tracks[0]), 0);
+        assert_eq!(peak(&tracks[2]), 0);
+        assert!(recording.channel_wav(4).is_none());
+    }
+
+    #[test]
+    fn mix_only_has_no_channels() {
+        let recording: Recording = before().record_audio(1, false);
+        assert!(!recording.has_channels());
+        assert!(recording.channel_wav(0).is_none());
+    }
+
+    #[test]
+    fn muted_channels_stay_on_their_track() {
+        let mut emu = before();
+        emu.mute_channel(1, true);
+        emu.mute_channel(3, true);
+        let recording = emu.record_audio(10, true);
+        assert_eq!(peak(&recording.wav()), 0);
+        assert!(peak(&recording.channel_wav(1).unwrap()) > 1000);
+    }
+
+    #[test]
+    fn leaves_frontend_audio_alone() {
+        let mut emu = before();
+        let recording = emu.record_audio(5, false);
+        let mut out = vec![0.0; RATE as usize * 2];
+        assert_eq!(emu.drain_audio(&mut out), recording.len() * 2);
+    }
+
+    #[test]
+    fn stop_without_start() {
+        let mut emu = before();
+        assert!(emu.stop_recording().is_none());
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod ram_cart;
+
+mod rewind_tests {
+    use crate::ram_cart::{before, run_frames};
+    use dmg_emu::{Emu, rewind::{diff, patch}};
+    use test_case::test_case;
+
+    fn record_frames(emu: &mut Emu, frames: usize) -> Vec<Vec<u8>> {
+        let mut states = vec![];
+        for _ in 0..frames {
+            emu.tick_till_frame_done();
+            emu.record_frame();
+            states.push(emu.save_state());
+        }
+        states
+    }
+
+    #[test_case(&[], &[1, 2, 3]                              ;  "from empty")]
+    #[test_case(&[1, 2, 3], &[]                              ;  "to empty")]
+    #[test_case(&[0; 32], &[0; 32]                           ;  "unchanged")]
+    #[test_case(&[0; 32], &[0, 0, 9, 0, 0, 9, 0, 0, 0, 0, 0] ;  "short gaps stay in one literal")]
+    #[test_case(&[5; 8], &[5, 5, 5, 5, 5, 5, 5, 5, 6, 7]     ;  "grows")]
+    fn delta_round_trips(base: &[u8], target: &[u8]) {
+        assert_eq!(patch(base, &diff(base, target)), target);
+    }
+
+    #[test]
+    fn delta_is_smaller_than_state() {
+        let mut emu = before(b"REWIND");
+        run_frames(&mut emu, 1);
+        let base = emu.save_state();
+        run_frames(&mut emu, 1);
+        let target = emu.save_state();
+        let delta = diff(&base, &target);
+        assert!(delta.len() < target.len() / 2);
+        assert_eq!(patch(&base, &delta), target);
+    }
+
+    #[test_case(1, 3, 3  ;  "every frame")]
+    #[test_case(1, 4, 4  ;  "exactly one interval")]
+    #[test_case(4, 6, 8  ;  "rounds back to an earlier snapshot")]
+    #[test_case(4, 1, 4  ;  "less than an interval")]
+    fn rewinds_to_captured_frame(interval: u32, frames: u32, rewound: u32) {
+        let mut emu = before(b"REWIND");
+        emu.enable_rewind(interval, 16 * 1024 * 1024);
+        let states = record_frames(&mut emu, 20);
+
+        assert_eq!(emu.rewind(frames).unwrap(), rewound);
+        assert_eq!(emu.save_state(), states[19 - rewound as usize]);
+    }
+
+    #[test]
+    fn rewinds_repeatedly_and_resumes() {
+        let mut emu = before(b"REWIND");
+        emu.enable_rewind(2, 16 * 1024 * 1024);
+        let states = record_frames(&mut emu, 12);
+
+        assert_eq!(emu.rewind(4).unwrap(), 4);
+        assert_eq!(emu.rewind(4).unwrap(), 4);
+        assert_eq!(emu.save_state(), states[3]);
+
+        let replayed = record_frames(&mut emu, 8);
+        assert_eq!(replayed, states[4..12].to_vec());
+    }
+
+    #[test]
+    fn stops_at_oldest_snapshot() {
+        let mut emu = before(b"REWIND");
+        emu.enable_rewind(1, 16 * 1024 * 1024);
+        let states = record_frames(&mut emu, 5);
+
+        assert_eq!(emu.rewind(100).unwrap(), 4);
+        assert_eq!(emu.save_state(), states[0]);
+        assert_eq!(emu.rewind_available(), 0);
+    }
+
+    #[test]
+    fn stays_within_budget() {
+        let mut emu = before(b"REWIND");
+        let state_size = emu.save_state().len();
+        let budget = state_size * 2;
+        emu.enable_rewind(1, budget);
+        record_frames(&mut emu, 60);
+
+        assert!(emu.rewind_memory() <= budget);
+        let available = emu.rewind_available();
+        assert!(available > 0 && available < 59);
+        assert_eq!(emu.rewind(1000).unwrap(), available);
+    }
+
+    #[test]
+    fn disabled_rewind_does_nothing() {
+        let mut emu = before(b"REWIND");
+        let states = record_frames(&mut emu, 3);
+        assert_eq!(emu.rewind(2).unwrap(), 0);
+        assert_eq!(emu.save_state(), states[2]);
+    }
+}
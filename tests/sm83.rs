@@ -0,0 +1,389 @@
+#[cfg(test)]
+
+extern crate test_case;
+
+// runs the CPU on its own against the SM83 single step test files, see
+// https://github.com/SingleStepTests/sm83 for the format
+mod single_step {
+    use dmg_emu::bus::Bus;
+    use dmg_emu::cpu::Cpu;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::panic;
+
+    // what the bus did during one M-cycle
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Cycle {
+        Idle,
+        Read(u16, u8),
+        Write(u16, u8),
+    }
+
+    impl fmt::Debug for Cycle {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Cycle::Idle => write!(f, "idle"),
+                Cycle::Read(addr, value) => write!(f, "read  {:04X} -> {:02X}", addr, value),
+                Cycle::Write(addr, value) => write!(f, "write {:04X} <- {:02X}", addr, value),
+            }
+        }
+    }
+
+    // flat 64 KiB with no IO behind it, logging every access the CPU makes
+    pub struct TestBus {
+        ram: Vec<u8>,
+        accesses: RefCell<Vec<Cycle>>,
+    }
+
+    impl TestBus {
+        fn new() -> Self {
+            Self { ram: vec![0; 0x10000], accesses: RefCell::new(vec![]) }
+        }
+
+        fn take_accesses(&mut self) -> Vec<Cycle> {
+            self.accesses.take()
+        }
+    }
+
+    impl Bus for TestBus {
+        fn get(&self, addr: u16) -> u8 {
+            let value = self.ram[addr as usize];
+            self.accesses.borrow_mut().push(Cycle::Read(addr, value));
+            value
+        }
+
+        fn set(&mut self, addr: u16, value: u8) {
+            self.ram[addr as usize] = value;
+            self.accesses.borrow_mut().push(Cycle::Write(addr, value));
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        // no timer on this bus
+        fn reset_div(&mut self) {}
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct State {
+        pub a: u8,
+        pub f: u8,
+        pub b: u8,
+        pub c: u8,
+        pub d: u8,
+        pub e: u8,
+        pub h: u8,
+        pub l: u8,
+        pub sp: u16,
+        pub pc: u16,
+        pub ime: bool,
+        // EI ran and IME comes on with the next instruction
+        pub ei: bool,
+        pub ram: Vec<(u16, u8)>,
+    }
+
+    pub struct Case {
+        pub name: String,
+        pub initial: State,
+        pub expected: State,
+        pub cycles: Vec<Cycle>,
+    }
+
+    fn number(value: &Value, key: &str) -> u64 {
+        value[key].as_u64().unwrap_or_else(|| panic!("missing field {}", key))
+    }
+
+    fn state(value: &Value) -> State {
+        let byte = |key| number(value, key) as u8;
+        let ram = value["ram"].as_array().expect("missing field ram")
+            .iter()
+            .map(|cell| (cell[0].as_u64().unwrap() as u16, cell[1].as_u64().unwrap() as u8))
+            .collect();
+        State {
+            a: byte("a"),
+            f: byte("f"),
+            b: byte("b"),
+            c: byte("c"),
+            d: byte("d"),
+            e: byte("e"),
+            h: byte("h"),
+            l: byte("l"),
+            sp: number(value, "sp") as u16,
+            pc: number(value, "pc") as u16,
+            ime: number(value, "ime") != 0,
+            ei: number(value, "ie") != 0,
+            ram,
+        }
+    }
+
+    // [addr, data, pins] where pins reads like "r-m" or "-wm", null or "---" when the bus is idle
+    fn cycle(value: &Value) -> Cycle {
+        let pins = value[2].as_str().unwrap_or("");
+        let addr = value[0].as_u64().unwrap_or(0) as u16;
+        let data = value[1].as_u64().unwrap_or(0) as u8;
+        if pins.contains('r') {
+            Cycle::Read(addr, data)
+        } else if pins.contains('w') {
+            Cycle::Write(addr, data)
+        } else {
+            Cycle::Idle
+        }
+    }
+
+    // one file of the SM83 single step tests, a list of cases for the same opcode
+    pub fn parse(json: &str) -> Vec<Case> {
+        let cases: Value = serde_json::from_str(json).expect("invalid test file");
+        cases.as_array().expect("test file is not a list")
+            .iter()
+            .map(|case| Case {
+                name: case["name"].as_str().unwrap_or_default().to_string(),
+                initial: state(&case["initial"]),
+                expected: state(&case["final"]),
+                cycles: case["cycles"].as_array().expect("missing field cycles").iter().map(cycle).collect(),
+            })
+            .collect()
+    }
+
+    // a panic in one case is reported like any other failure instead of ending the whole file
+    pub fn run(case: &Case) -> Result<(), String> {
+        panic::catch_unwind(|| step(case)).unwrap_or_else(|_| Err(format!("{}\n  panicked", case.name)))
+    }
+
+    // runs one instruction from a clean start and returns every difference to the expected state
+    fn step(case: &Case) -> Result<(), String> {
+        let mut bus = TestBus::new();
+        for &(addr, value) in &case.initial.ram {
+            bus.ram[addr as usize] = value;
+        }
+
+        let initial = &case.initial;
+        let mut cpu = Cpu::new();
+        cpu.AF = (initial.a as u16) << 8 | initial.f as u16;
+        cpu.BC = (initial.b as u16) << 8 | initial.c as u16;
+        cpu.DE = (initial.d as u16) << 8 | initial.e as u16;
+        cpu.HL = (initial.h as u16) << 8 | initial.l as u16;
+        cpu.SP = initial.sp;
+        cpu.PC = initial.pc;
+        cpu.ime = initial.ime;
+        cpu.ei_pending = initial.ei;
+
+        let mut errors = vec![];
+        for (i, expected) in case.cycles.iter().enumerate() {
+            cpu.tick(&mut bus);
+            let accesses = bus.take_accesses();
+            let actual = match accesses.as_slice() {
+                [] => Cycle::Idle,
+                [access] => *access,
+                _ => {
+                    errors.push(format!("cycle {}: several accesses {:?}", i + 1, accesses));
+                    continue;
+                }
+            };
+            if actual != *expected {
+                errors.push(format!("cycle {}: expected {:?}, got {:?}", i + 1, expected, actual));
+            }
+        }
+
+        let ram = case.expected.ram.iter().map(|&(addr, _)| (addr, bus.ram[addr as usize])).collect();
+        let actual = State {
+            a: (cpu.AF >> 8) as u8,
+            f: cpu.AF as u8,
+            b: (cpu.BC >> 8) as u8,
+            c: cpu.BC as u8,
+            d: (cpu.DE >> 8) as u8,
+            e: cpu.DE as u8,
+            h: (cpu.HL >> 8) as u8,
+            l: cpu.HL as u8,
+            sp: cpu.SP,
+            pc: cpu.PC,
+            ime: cpu.ime,
+            ei: cpu.ei_pending,
+            ram,
+        };
+        if actual != case.expected {
+            errors.push(format!("expected {:?}\n     got {:?}", case.expected, actual));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{}\n  {}", case.name, errors.join("\n  ")))
+        }
+    }
+}
+
+mod single_step_tests {
+    use crate::single_step::{parse, run, Cycle};
+    use test_case::test_case;
+
+    // LD (HL), B then NOP at 0xC000, the shape of a case in the community test files
+    const LD_HL_B: &str = r#"[{
+        "name": "70 0000",
+        "initial": {
+            "pc": 49152, "sp": 65534, "a": 1, "b": 66, "c": 3, "d": 4, "e": 5, "f": 176, "h": 208, "l": 0,
+            "ime": 0, "ie": 0, "ram": [[49152, 112], [49153, 0], [53248, 0]]
+        },
+        "final": {
+            "pc": 49153, "sp": 65534, "a": 1, "b": 66, "c": 3, "d": 4, "e": 5, "f": 176, "h": 208, "l": 0,
+            "ime": 0, "ie": 0, "ram": [[49152, 112], [49153, 0], [53248, 66]]
+        },
+        "cycles": [[49152, 112, "r-m"], [53248, 66, "-wm"]]
+    }]"#;
+
+    fn case(json: &str) -> String {
+        let cases = parse(json);
+        assert_eq!(cases.len(), 1);
+        match run(&cases[0]) {
+            Ok(()) => String::new(),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn parses_a_test_file() {
+        let cases = parse(LD_HL_B);
+        assert_eq!(cases[0].name, "70 0000");
+        assert_eq!(cases[0].initial.b, 0x42);
+        assert_eq!(cases[0].initial.pc, 0xC000);
+        assert_eq!(cases[0].expected.ram[2], (0xD000, 0x42));
+        assert_eq!(cases[0].cycles, [Cycle::Read(0xC000, 0x70), Cycle::Write(0xD000, 0x42)]);
+    }
+
+    #[test_case("null"                    ;  "null")]
+    #[test_case(r#"[53248, null, "---"]"# ;  "no pins")]
+    fn idle_cycles(idle: &str) {
+        let json = LD_HL_B.replace(r#"[53248, 66, "-wm"]"#, idle);
+        assert_eq!(parse(&json)[0].cycles, [Cycle::Read(0xC000, 0x70), Cycle::Idle]);
+    }
+
+    #[test]
+    fn passing_case() {
+        assert_eq!(case(LD_HL_B), "");
+    }
+
+    #[test]
+    fn reports_a_wrong_bus_cycle() {
+        let json = LD_HL_B.replace(r#"[53248, 66, "-wm"]"#, r#"[53249, 66, "-wm"]"#);
+        let message = case(&json);
+        assert!(message.starts_with("70 0000"));
+        assert!(message.contains("cycle 2: expected write D001 <- 42, got write D000 <- 42"));
+    }
+
+    #[test]
+    fn reports_a_missing_cycle() {
+        let json = LD_HL_B.replace(r#""-wm"]]"#, r#""-wm"], null]"#);
+        assert!(case(&json).contains("cycle 3: expected idle, got read  C001 -> 00"));
+    }
+
+    #[test]
+    fn reports_a_wrong_final_state() {
+        let json = LD_HL_B.replace(r#""pc": 49153"#, r#""pc": 49154"#);
+        assert!(case(&json).contains("pc: 49154"));
+    }
+
+    // one case with B = 0x42 and HL = 0xD000, the fields given are the ones that differ between fixtures
+    fn vector(initial: &str, expected: &str, cycles: &str) -> String {
+        let registers = r#""a": 1, "b": 66, "c": 3, "d": 4, "e": 5, "f": 176, "h": 208, "l": 0"#;
+        format!(
+            r#"[{{"name": "fixture", "initial": {{{}, {}}}, "final": {{{}, {}}}, "cycles": {}}}]"#,
+            registers, initial, registers, expected, cycles
+        )
+    }
+
+    #[test]
+    fn ei_is_still_pending_after_it_runs() {
+        let json = vector(
+            r#""pc": 49152, "sp": 65534, "ime": 0, "ie": 0, "ram": [[49152, 251]]"#,
+            r#""pc": 49153, "sp": 65534, "ime": 0, "ie": 1, "ram": [[49152, 251]]"#,
+            r#"[[49152, 251, "r-m"]]"#,
+        );
+        assert_eq!(case(&json), "");
+    }
+
+    #[test]
+    fn pending_ei_comes_on_with_the_next_instruction() {
+        let json = vector(
+            r#""pc": 49152, "sp": 65534, "ime": 0, "ie": 1, "ram": [[49152, 0]]"#,
+            r#""pc": 49153, "sp": 65534, "ime": 1, "ie": 0, "ram": [[49152, 0]]"#,
+            r#"[[49152, 0, "r-m"]]"#,
+        );
+        assert_eq!(case(&json), "");
+    }
+
+    // IE and IF are just bytes in the vectors, the CPU alone doesn't dispatch on them
+    #[test]
+    fn interrupt_lines_are_plain_ram() {
+        let json = vector(
+            r#""pc": 49152, "sp": 65534, "ime": 1, "ie": 0, "ram": [[49152, 112], [65295, 31], [65535, 31]]"#,
+            r#""pc": 49153, "sp": 65534, "ime": 1, "ie": 0, "ram": [[49152, 112], [53248, 66], [65295, 31]]"#,
+            r#"[[49152, 112, "r-m"], [53248, 66, "-wm"]]"#,
+        );
+        assert_eq!(case(&json), "");
+    }
+
+    #[test]
+    fn stop_resets_div_without_a_bus_write() {
+        let json = vector(
+            r#""pc": 49152, "sp": 65534, "ime": 0, "ie": 0, "ram": [[49152, 16], [65284, 171]]"#,
+            r#""pc": 49154, "sp": 65534, "ime": 0, "ie": 0, "ram": [[49152, 16], [65284, 171]]"#,
+            r#"[[49152, 16, "r-m"]]"#,
+        );
+        assert_eq!(case(&json), "");
+    }
+
+    // PUSH BC with SP at 1 goes through 0x0000 to 0xFFFF
+    #[test]
+    fn push_wraps_the_stack_pointer() {
+        let json = vector(
+            r#""pc": 49152, "sp": 1, "ime": 0, "ie": 0, "ram": [[49152, 197]]"#,
+            r#""pc": 49153, "sp": 65535, "ime": 0, "ie": 0, "ram": [[0, 66], [65535, 3]]"#,
+            r#"[[49152, 197, "r-m"], null, [0, 66, "-wm"], [65535, 3, "-wm"]]"#,
+        );
+        assert_eq!(case(&json), "");
+    }
+}
+
+mod sm83_test {
+
+    use crate::single_step::{parse, run};
+    use dmg_emu::disasm;
+    use test_case::test_case;
+    use std::{env, fs};
+
+    // at most this many failing cases are printed per opcode
+    const SHOWN: usize = 3;
+
+    // the files are named 00.json to ff.json and cb 00.json to cb ff.json
+    fn run_file(name: &str) -> Option<String> {
+        let mut path = format!("./resources/{}", name);
+        if let Ok(dir) = env::var("SM83_TEST_PATH") {
+            path = format!("./resources/{}{}", dir, name);
+        }
+        let json = fs::read_to_string(&path).unwrap_or_else(|e| panic!("could not read {}: {}", path, e));
+        let cases = parse(&json);
+
+        let failures: Vec<String> = cases.iter().filter_map(|case| run(case).err()).collect();
+        if failures.is_empty() {
+            return None;
+        }
+        let shown = failures.iter().take(SHOWN).cloned().collect::<Vec<_>>().join("\n");
+        Some(format!("{}: {} of {} cases failed\n{}", name, failures.len(), cases.len(), shown))
+    }
+
+    #[test_case(false ;  "base opcodes")]
+    #[test_case(true  ;  "cb opcodes")]
+    fn sm83_test(cb: bool) {
+        let failures: Vec<String> = (0..=0xFFu8)
+            // the illegal opcodes have no test files, and CB is only a prefix
+            .filter(|&op| cb || (op != 0xCB && disasm::cycles(op, 0).0 != 0))
+            .filter_map(|op| match cb {
+                true => run_file(&format!("cb {:02x}.json", op)),
+                false => run_file(&format!("{:02x}.json", op)),
+            })
+            .collect();
+        assert!(failures.is_empty(), "{} opcodes failed\n\n{}", failures.len(), failures.join("\n\n"));
+    }
+
+}
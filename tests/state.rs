@@ -0,0 +1,91 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod ram_cart;
+
+mod state_tests {
+    use crate::ram_cart::{before, run_frames};
+    use dmg_emu::{Emu, error::EmuError};
+    use test_case::test_case;
+
+    // frames end on whatever M-cycle the PPU finishes, usually mid instruction
+    fn run_until_mid_instruction(emu: &mut Emu) {
+        for _ in 0..100 {
+            emu.tick_till_frame_done();
+            if emu.cpu().get_cycle() != 1 {
+                return;
+            }
+        }
+        panic!("never stopped inside an instruction");
+    }
+
+    #[test]
+    fn resumes_mid_instruction() {
+        let mut emu = before(b"STATE");
+        run_until_mid_instruction(&mut emu);
+        let state = emu.save_state();
+
+        run_frames(&mut emu, 5);
+        let expected = emu.save_state();
+
+        emu.load_state(&state).unwrap();
+        assert_eq!(emu.save_state(), state);
+        run_frames(&mut emu, 5);
+        assert_eq!(emu.save_state(), expected);
+    }
+
+    #[test]
+    fn loads_into_a_fresh_emulator() {
+        let mut emu = before(b"STATE");
+        run_until_mid_instruction(&mut emu);
+        let state = emu.save_state();
+
+        let mut other = before(b"STATE");
+        other.load_state(&state).unwrap();
+        assert_eq!(other.get_buffer(), emu.get_buffer());
+        assert_eq!(other.export_save(0.0), emu.export_save(0.0));
+
+        run_frames(&mut emu, 2);
+        run_frames(&mut other, 2);
+        assert_eq!(other.save_state(), emu.save_state());
+    }
+
+    #[test]
+    fn rejects_other_cartridge() {
+        let state = before(b"STATE").save_state();
+        let mut emu = before(b"OTHER");
+        assert_eq!(emu.load_state(&state), Err(EmuError::StateCartMismatch));
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut emu = before(b"STATE");
+        let mut state = emu.save_state();
+        state[4] = 0xFF;
+        assert_eq!(emu.load_state(&state), Err(EmuError::UnsupportedStateVersion(0x00FF)));
+    }
+
+    #[test_case(0    ;  "empty")]
+    #[test_case(4    ;  "magic only")]
+    #[test_case(20   ;  "inside the cpu")]
+    #[test_case(9000 ;  "inside memory")]
+    fn truncated_state_leaves_machine_untouched(length: usize) {
+        let mut emu = before(b"STATE");
+        run_frames(&mut emu, 1);
+        let mut state = emu.save_state();
+        state.truncate(length);
+
+        run_frames(&mut emu, 1);
+        let current = emu.save_state();
+        assert_eq!(emu.load_state(&state), Err(EmuError::CorruptState));
+        assert_eq!(emu.save_state(), current);
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut emu = before(b"STATE");
+        let mut state = emu.save_state();
+        state.push(0);
+        assert_eq!(emu.load_state(&state), Err(EmuError::CorruptState));
+    }
+}
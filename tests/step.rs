@@ -0,0 +1,96 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod cpu_assert;
+
+mod step_tests {
+    use crate::cpu_assert;
+    use dmg_emu::{Emu, mem::MemAccess};
+    use test_case::test_case;
+
+    fn before(op_codes: &[u8]) -> Emu {
+        cpu_assert::before(op_codes, 0xFFFE)
+    }
+
+    fn read(cycle: u8, addr: u16, value: u8) -> MemAccess {
+        MemAccess { cycle, addr, value, write: false }
+    }
+
+    fn write(cycle: u8, addr: u16, value: u8) -> MemAccess {
+        MemAccess { cycle, addr, value, write: true }
+    }
+
+    #[test]
+    fn records_an_instruction() {
+        let mut emu = before(&[0x01, 0x20, 0x30]);
+        let step = emu.step_instruction();
+        assert_eq!(step.pc, 0x0000);
+        assert_eq!(step.bytes(), vec![0x01, 0x20, 0x30]);
+        assert_eq!(step.mnemonic(), "LD BC,$3020");
+        assert_eq!(step.cycles, 3);
+        assert!(!step.interrupt);
+        assert_eq!(step.before.BC, 0x0000);
+        assert_eq!(step.after.BC, 0x3020);
+        assert_eq!(step.after.PC, 0x0003);
+        assert_eq!(step.accesses(), vec![read(1, 0x0000, 0x01), read(2, 0x0001, 0x20), read(3, 0x0002, 0x30)]);
+    }
+
+    // PUSH spends a cycle on SP before the writes
+    #[test]
+    fn records_writes() {
+        let mut emu = before(&[0xC5]);
+        emu.cpu().BC = 0x1234;
+        let step = emu.step_instruction();
+        assert_eq!(step.mnemonic(), "PUSH BC");
+        assert_eq!(step.cycles, 4);
+        assert_eq!(step.accesses(), vec![read(1, 0x0000, 0xC5), write(3, 0xFFFD, 0x12), write(4, 0xFFFC, 0x34)]);
+    }
+
+    #[test_case(&[0x18, 0xFE], "JR $0000"         ;  "relative jump target")]
+    #[test_case(&[0xE0, 0x44], "LDH ($FF44),A"    ;  "high page address")]
+    #[test_case(&[0xF8, 0xFE], "LD HL,SP-2"       ;  "signed stack offset")]
+    #[test_case(&[0xCB, 0x7C], "BIT 7,H"          ;  "prefixed bit test")]
+    #[test_case(&[0xCB, 0x36], "SWAP (HL)"        ;  "prefixed rotate")]
+    fn decodes_the_mnemonic(op_codes: &[u8], mnemonic: &str) {
+        let mut emu = before(op_codes);
+        let step = emu.step_instruction();
+        assert_eq!(step.bytes(), op_codes.to_vec());
+        assert_eq!(step.mnemonic(), mnemonic);
+    }
+
+    #[test]
+    fn steps_are_instruction_sized() {
+        let mut emu = before(&[0x00, 0x3E, 0x42, 0xCD, 0x10, 0x00]);
+        let cycles: Vec<u32> = (0..3).map(|_| emu.step_instruction().cycles).collect();
+        assert_eq!(cycles, vec![1, 2, 6]);
+        assert_eq!(emu.cpu().PC, 0x0010);
+    }
+
+    #[test]
+    fn records_an_interrupt_dispatch() {
+        let mut emu = before(&[0x00]);
+        emu.cpu().ime = true;
+        emu.mem().set(0xFFFF, 0x04);
+        emu.mem().set(0xFF0F, 0x04);
+        let step = emu.step_instruction();
+        assert!(step.interrupt);
+        assert_eq!(step.mnemonic(), "INT $50");
+        assert!(step.bytes().is_empty());
+        assert_eq!(step.cycles, 5);
+        assert_eq!(step.after.PC, 0x0050);
+        let pushes: Vec<MemAccess> = step.accesses().into_iter().filter(|a| a.addr != 0xFF0F).collect();
+        assert_eq!(pushes, vec![write(3, 0xFFFD, 0x00), write(4, 0xFFFC, 0x00)]);
+    }
+
+    #[test]
+    fn halted_cpu_waits_a_cycle() {
+        let mut emu = before(&[0x76, 0x00]);
+        emu.mem().set(0xFFFF, 0x00);
+        emu.step_instruction();
+        let step = emu.step_instruction();
+        assert_eq!(step.mnemonic(), "HALT");
+        assert_eq!(step.cycles, 1);
+        assert!(step.accesses().is_empty());
+        assert_eq!(step.after.PC, 0x0001);
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod mooneye;
+
+mod timer_tests {
+    use dmg_emu::Emu;
+    use test_case::test_case;
+
+    const DIV: u16 = 0xFF04;
+    const TIMA: u16 = 0xFF05;
+    const TMA: u16 = 0xFF06;
+    const TAC: u16 = 0xFF07;
+    const IF: u16 = 0xFF0F;
+
+    // TAC 0x05 clocks TIMA every 4 M-cycles
+    fn before(tima: u8) -> Emu {
+        let mut emu = Emu::new();
+        emu.mem().set(TMA, 0x42);
+        emu.mem().set(TAC, 0x05);
+        emu.mem().set(DIV, 0);
+        emu.mem().set(TIMA, tima);
+        emu
+    }
+
+    fn run(emu: &mut Emu, cycles: usize) {
+        for _ in 0..cycles {
+            emu.mem().tick();
+        }
+    }
+
+    fn timer_requested(emu: &mut Emu) -> bool {
+        emu.mem().get(IF) & 4 != 0
+    }
+
+    #[test]
+    fn overflow_requests_interrupt() {
+        let mut emu = before(0xFF);
+        run(&mut emu, 4);
+        assert_eq!(emu.mem().get(TIMA), 0);
+        assert!(!timer_requested(&mut emu));
+
+        run(&mut emu, 1);
+        assert_eq!(emu.mem().get(TIMA), 0x42);
+        assert!(timer_requested(&mut emu));
+    }
+
+    #[test]
+    fn write_before_reload_cancels_it() {
+        let mut emu = before(0xFF);
+        run(&mut emu, 4);
+        emu.mem().set(TIMA, 0x10);
+        run(&mut emu, 1);
+        assert_eq!(emu.mem().get(TIMA), 0x10);
+        assert!(!timer_requested(&mut emu));
+    }
+
+    #[test]
+    fn write_on_reload_cycle_is_ignored() {
+        let mut emu = before(0xFF);
+        run(&mut emu, 5);
+        emu.mem().set(TIMA, 0x10);
+        assert_eq!(emu.mem().get(TIMA), 0x42);
+        assert!(timer_requested(&mut emu));
+    }
+
+    #[test]
+    fn tma_write_on_reload_cycle_reaches_tima() {
+        let mut emu = before(0xFF);
+        run(&mut emu, 5);
+        emu.mem().set(TMA, 0x77);
+        assert_eq!(emu.mem().get(TIMA), 0x77);
+    }
+
+    // DIV bit 3 is high after 2 M-cycles, resetting DIV makes it fall
+    #[test_case(2, 1 ;  "selected bit high")]
+    #[test_case(1, 0 ;  "selected bit low")]
+    fn div_reset_glitch(cycles: usize, increments: u8) {
+        let mut emu = before(0);
+        run(&mut emu, cycles);
+        emu.mem().set(DIV, 0);
+        assert_eq!(emu.mem().get(TIMA), increments);
+    }
+
+    #[test]
+    fn disabling_timer_on_high_bit_increments() {
+        let mut emu = before(0);
+        run(&mut emu, 2);
+        emu.mem().set(TAC, 0x01);
+        assert_eq!(emu.mem().get(TIMA), 1);
+        run(&mut emu, 16);
+        assert_eq!(emu.mem().get(TIMA), 1);
+    }
+}
+
+mod mooneye_timer_test {
+
+    use crate::mooneye::{init, run_rom};
+    use test_case::test_case;
+
+    #[test_case("div_write.gb"            ;  "div_write.gb")]
+    #[test_case("rapid_toggle.gb"         ;  "rapid_toggle.gb")]
+    #[test_case("tim00.gb"                ;  "tim00.gb")]
+    #[test_case("tim00_div_trigger.gb"    ;  "tim00_div_trigger.gb")]
+    #[test_case("tim01.gb"                ;  "tim01.gb")]
+    #[test_case("tim01_div_trigger.gb"    ;  "tim01_div_trigger.gb")]
+    #[test_case("tim10.gb"                ;  "tim10.gb")]
+    #[test_case("tim10_div_trigger.gb"    ;  "tim10_div_trigger.gb")]
+    #[test_case("tim11.gb"                ;  "tim11.gb")]
+    #[test_case("tim11_div_trigger.gb"    ;  "tim11_div_trigger.gb")]
+    #[test_case("tima_reload.gb"          ;  "tima_reload.gb")]
+    #[test_case("tima_write_reloading.gb" ;  "tima_write_reloading.gb")]
+    #[test_case("tma_write_reloading.gb"  ;  "tma_write_reloading.gb")]
+    fn mooneye_timer_test(name: &str) {
+        let mut emu = init(name);
+        run_rom(&mut emu);
+    }
+
+}
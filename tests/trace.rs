@@ -0,0 +1,175 @@
+#[cfg(test)]
+
+extern crate test_case;
+mod doctor;
+mod ram_cart;
+
+mod trace_tests {
+    use crate::doctor::{assert_trace, first_divergence, trace};
+    use crate::ram_cart::{before, run_frames};
+    use dmg_emu::Emu;
+
+    const CART_TRACE: [&str; 4] = [
+        "A:11 F:80 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0100 PCMEM:00,C3,50,01",
+        "A:11 F:80 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0101 PCMEM:C3,50,01,00",
+        "A:11 F:80 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0150 PCMEM:3E,0A,EA,00",
+        "A:0A F:80 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0152 PCMEM:EA,00,00,21",
+    ];
+
+    #[test]
+    fn logs_each_instruction_before_it_runs() {
+        let mut emu = before(b"TRACE");
+        let log = trace(&mut emu);
+        for _ in 0..4 {
+            emu.tick();
+        }
+        assert_eq!(log.take_lines(), CART_TRACE);
+    }
+
+    #[test]
+    fn tracing_does_not_change_the_run() {
+        let mut traced = before(b"TRACE");
+        let mut plain = before(b"TRACE");
+        let log = trace(&mut traced);
+        run_frames(&mut traced, 2);
+        run_frames(&mut plain, 2);
+        assert_eq!(traced.get_cpu_state(), plain.get_cpu_state());
+        assert!(!log.take_lines().is_empty());
+    }
+
+    #[test]
+    fn clearing_stops_the_log() {
+        let mut emu = before(b"TRACE");
+        let log = trace(&mut emu);
+        emu.tick();
+        assert!(emu.clear_tracer().is_some());
+        emu.tick();
+        assert_eq!(log.take_lines(), CART_TRACE[..1]);
+    }
+
+    // EI, HALT, NOP with RETI at the timer vector
+    #[test]
+    fn halts_and_dispatches_are_not_logged() {
+        let mut emu = Emu::new();
+        let mut program = [0; 50];
+        program[..3].copy_from_slice(&[0xFB, 0x76, 0x00]);
+        emu.write_mem(&program);
+        emu.mem().set(0x0050, 0xD9);
+        emu.mem().set(0xFFFF, 0x04);
+        emu.cpu().SP = 0xD000;
+        let log = trace(&mut emu);
+
+        for _ in 0..10 {
+            emu.tick();
+        }
+        let pcs: Vec<String> = log.take_lines().iter().map(|line| line[48..55].to_string()).collect();
+        assert_eq!(pcs, ["PC:0000", "PC:0001"]);
+
+        emu.mem().set(0xFF0F, 0x04);
+        for _ in 0..4 {
+            emu.tick();
+        }
+        let pcs: Vec<String> = log.take_lines().iter().map(|line| line[48..55].to_string()).collect();
+        assert_eq!(pcs, ["PC:0050", "PC:0002"]);
+    }
+
+    #[test]
+    fn matching_reference_passes() {
+        let mut emu = before(b"TRACE");
+        assert_trace(&mut emu, &CART_TRACE.join("\n"));
+    }
+
+    #[test]
+    fn reports_the_first_diverging_instruction() {
+        let mut reference = CART_TRACE.to_vec();
+        reference[2] = "A:11 F:80 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0151 PCMEM:0A,EA,00,00";
+        let mut emu = before(b"TRACE");
+
+        let divergence = first_divergence(&mut emu, &reference.join("\n")).unwrap();
+        assert_eq!(divergence.line, 3);
+        assert_eq!(divergence.previous.as_deref(), Some(CART_TRACE[1]));
+        assert_eq!(divergence.expected, reference[2]);
+        assert_eq!(divergence.actual.as_deref(), Some(CART_TRACE[2]));
+    }
+
+    #[test]
+    fn reports_a_locked_up_cpu() {
+        let mut emu = Emu::new();
+        let mut program = [0; 50];
+        program[0] = 0xD3;
+        emu.write_mem(&program);
+        let reference = [
+            "A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0000 PCMEM:D3,00,00,00",
+            "A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0001 PCMEM:00,00,00,00",
+        ];
+
+        let divergence = first_divergence(&mut emu, &reference.join("\n")).unwrap();
+        assert_eq!(divergence.line, 2);
+        assert_eq!(divergence.actual, None);
+        assert!(divergence.to_string().contains("stopped running instructions"));
+    }
+
+    #[test]
+    fn stubbed_ly_is_only_seen_by_the_cpu() {
+        let mut emu = Emu::new();
+        emu.mem().set_ly(0x12);
+        emu.mem().stub_ly(Some(0x90));
+        assert_eq!(emu.mem().get(0xFF44), 0x90);
+
+        emu.mem().ppu_access = true;
+        assert_eq!(emu.mem().get(0xFF44), 0x12);
+        emu.mem().ppu_access = false;
+
+        emu.mem().stub_ly(None);
+        assert_eq!(emu.mem().get(0xFF44), 0x12);
+    }
+}
+
+mod doctor_cpu_test {
+
+    use crate::doctor::assert_trace;
+    use dmg_emu::Emu;
+    use test_case::test_case;
+    use std::{env, fs};
+
+    // gameboy-doctor logs start from the DMG boot state with LY stuck at 0x90
+    fn init(name: &str) -> Emu {
+        let mut rom = name.to_string();
+        if let Ok(path) = env::var("TEST_ROM_PATH") {
+            rom = path + rom.as_str();
+        }
+
+        let mut emu = Emu::new();
+        emu.load_rom(format!("./resources/{}", rom)).unwrap();
+        emu.cpu().PC = 0x0100;
+        emu.cpu().SP = 0xFFFE;
+        emu.cpu().AF = 0x01B0;
+        emu.cpu().BC = 0x0013;
+        emu.cpu().DE = 0x00D8;
+        emu.cpu().HL = 0x014D;
+        emu.mem().stub_ly(Some(0x90));
+        emu
+    }
+
+    fn reference(log: &str) -> String {
+        let path = env::var("DOCTOR_LOG_PATH").unwrap_or_default() + log;
+        fs::read_to_string(&path).unwrap_or_else(|e| panic!("could not read {}: {}", path, e))
+    }
+
+    #[test_case("01-special.gb",            "1.log"  ;  "01-special.gb")]
+    #[test_case("02-interrupts.gb",         "2.log"  ;  "02-interrupts.gb")]
+    #[test_case("03-op sp,hl.gb",           "3.log"  ;  "03-op sp,hl.gb")]
+    #[test_case("04-op r,imm.gb",           "4.log"  ;  "04-op r,imm.gb")]
+    #[test_case("05-op rp.gb",              "5.log"  ;  "05-op rp.gb")]
+    #[test_case("06-ld r,r.gb",             "6.log"  ;  "06-ld r,r.gb")]
+    #[test_case("07-jr,jp,call,ret,rst.gb", "7.log"  ;  "07-jr,jp,call,ret,rst.gb")]
+    #[test_case("08-misc instrs.gb",        "8.log"  ;  "08-misc instrs.gb")]
+    #[test_case("09-op r,r.gb",             "9.log"  ;  "09-op r,r.gb")]
+    #[test_case("10-bit ops.gb",            "10.log" ;  "10-bit ops.gb")]
+    #[test_case("11-op a,(hl).gb",          "11.log" ;  "11-op a,(hl).gb")]
+    fn doctor_cpu_test(name: &str, log: &str) {
+        let mut emu = init(name);
+        assert_trace(&mut emu, &reference(log));
+    }
+
+}
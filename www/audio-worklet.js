@@ -0,0 +1,39 @@
+// Plays the ring the wasm AudioSink writes into, see src/audio_sink.rs for the layout
+const READ_INDEX = 0;
+const WRITE_INDEX = 1;
+const UNDERRUNS = 2;
+const CONTROL_WORDS = 4;
+
+class DMGAudioProcessor extends AudioWorkletProcessor {
+    constructor(options) {
+        super();
+        let buffer = options.processorOptions.buffer;
+        this.control = new Int32Array(buffer, 0, CONTROL_WORDS);
+        this.samples = new Float32Array(buffer, CONTROL_WORDS * 4);
+        this.capacity = this.samples.length;
+    }
+
+    process(inputs, outputs) {
+        let [left, right] = outputs[0];
+        let read = Atomics.load(this.control, READ_INDEX);
+        let write = Atomics.load(this.control, WRITE_INDEX);
+        let available = (write - read + this.capacity) % this.capacity / 2;
+        let frames = Math.min(available, left.length);
+
+        for (let i = 0; i < frames; i++) {
+            left[i] = this.samples[read];
+            right[i] = this.samples[read + 1];
+            read = (read + 2) % this.capacity;
+        }
+        left.fill(0, frames);
+        right.fill(0, frames);
+
+        if (frames < left.length) {
+            Atomics.add(this.control, UNDERRUNS, 1);
+        }
+        Atomics.store(this.control, READ_INDEX, read);
+        return true;
+    }
+}
+
+registerProcessor('dmg-audio', DMGAudioProcessor);
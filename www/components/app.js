@@ -1,4 +1,7 @@
-import { Emu } from "dmg-emu";
+import { Emu, AudioSink } from "dmg-emu";
+
+// a sixth of a second of stereo samples at 48kHz
+const AUDIO_CAPACITY = 16384;
 import { LitElement, html, css } from "lit-element";
 
 class App extends LitElement {
@@ -111,6 +114,8 @@ class App extends LitElement {
             PC: 0,
         };
         this.dmg = Emu.new();
+        // the worklet shares memory with the page, which needs cross origin isolation
+        this.sink = self.crossOriginIsolated ? AudioSink.new(AUDIO_CAPACITY) : null;
     }
 
     _handleFrame() {
@@ -138,6 +143,11 @@ class App extends LitElement {
         this.play = false;
     }
 
+    handleFault(e) {
+        let { pc, opcode } = e.detail;
+        alert(`CPU locked up on illegal opcode ${this.formatHex(opcode)} at ${this.formatHex(pc)}`);
+    }
+
     handleTrace() {
         this.shadowRoot.querySelector('dmg-screen').handleStep();
     }
@@ -164,12 +174,26 @@ class App extends LitElement {
         `
     }
 
+    get audioStats() {
+        if (!this.sink) {
+            return null;
+        }
+        return {
+            buffered: this.sink.buffered(),
+            underruns: this.sink.underruns(),
+            overruns: this.sink.overruns(),
+        };
+    }
+
     _renderIo() {
-        const titles = ["Serial", "Joypad", "Timer"];
+        const titles = ["Serial", "Joypad", "Timer", "Cartridge", "Audio", "GBS"];
         const elements = [    
             html`<serial-debug .buffer=${this.dmg.get_serial_buffer()}></serial-debug>`,
             html`<joypad-debug .dmg=${this.dmg} .data=${this.mem}></joypad-debug>`,
-            html`<timer-debug .timerIO=${this.dmg.get_timer_state()}></timer-debug>`
+            html`<timer-debug .timerIO=${this.dmg.get_timer_state()}></timer-debug>`,
+            html`<cartridge-debug .info=${this.dmg.get_cartridge_info()}></cartridge-debug>`,
+            html`<audio-debug .dmg=${this.dmg} .sink=${this.audioStats} .channels=${[0, 1, 2, 3].map(c => this.dmg.get_channel_status(c))}></audio-debug>`,
+            html`<gbs-player .dmg=${this.dmg} .info=${this.dmg.get_gbs_info()} .track=${this.dmg.current_track()}></gbs-player>`
         ]
         return html`<tabbed-card .titles=${titles} .elements=${elements}></tabbed-card>`;
     }
@@ -213,8 +237,10 @@ class App extends LitElement {
                 .stepping=${this.stepping} 
                 @frame=${this._handleFrame}
                 @break=${this.handlePause}
+                @fault=${this.handleFault}
                 @update-memory=${this._handleUpdateMemory}
-                .dmg=${this.dmg}></dmg-screen>
+                .dmg=${this.dmg}
+                .sink=${this.sink}></dmg-screen>
         </div> 
     `
     }
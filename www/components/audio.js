@@ -0,0 +1,66 @@
+import { LitElement, html, css } from "lit-element";
+
+class Audio extends LitElement {
+    static get properties() {
+        return {
+            sink: {attribute: false},
+            channels: {attribute: false},
+            dmg: {attribute: false}
+        }
+    }
+
+    static get styles() {
+        return css`
+            :host {
+                width: calc(100% - 10px);
+                padding: 10px;
+            }
+            .row {
+                margin-bottom: 10px;
+            }
+        `;
+    }
+
+    handleMute(channel, e) {
+        this.dmg.mute_channel(channel, e.target.checked);
+    }
+
+    handleSolo(channel, e) {
+        this.dmg.solo_channel(channel, e.target.checked);
+    }
+
+    _renderChannel(status, channel) {
+        return html`
+            <div class="row">
+                <span>CH${channel + 1}: ${status.enabled ? 'ON' : 'OFF'}</span>
+                <span>${status.frequency.toFixed(1)} Hz</span>
+                <span>VOL: ${status.volume}</span>
+                ${channel < 2 ? html`<span>DUTY: ${status.duty}</span>` : ''}
+                <label><input type="checkbox" @change=${e => this.handleMute(channel, e)}>Mute</label>
+                <label><input type="checkbox" @change=${e => this.handleSolo(channel, e)}>Solo</label>
+            </div>
+        `
+    }
+
+    _renderSink() {
+        if (!this.sink) {
+            return html`<span>Audio needs a cross origin isolated page</span>`;
+        }
+        return html`
+            <div class="row">
+                <span>Buffered: ${this.sink.buffered}</span>
+            </div>
+            <span>Underruns: ${this.sink.underruns}</span>
+            <span>Overruns: ${this.sink.overruns}</span>
+        `
+    }
+
+    render() {
+        return html`
+            ${(this.channels || []).map((status, channel) => this._renderChannel(status, channel))}
+            ${this._renderSink()}
+        `
+    }
+}
+
+customElements.define('audio-debug', Audio);
@@ -0,0 +1,73 @@
+import { LitElement, html, css } from "lit-element";
+import { CgbSupport } from "dmg-emu";
+
+class Cartridge extends LitElement {
+    static get properties() {
+        return {
+            info: {attribute: false}
+        }
+    }
+
+    static get styles() {
+        return css`
+            :host {
+                width: calc(100% - 10px);
+                padding: 10px;
+            }
+            .row {
+                margin-bottom: 10px;
+            }
+            .bad {
+                color: #E05F5F;
+            }
+        `;
+    }
+
+    formatHex(n, width) {
+        return n.toString(16).toUpperCase().padStart(width, '0');
+    }
+
+    get cgb() {
+        return this.info.cgb === CgbSupport.Only ? 'CGB only'
+            : this.info.cgb === CgbSupport.Enhanced ? 'CGB enhanced'
+            : 'DMG';
+    }
+
+    get licensee() {
+        return this.info.old_licensee_code === 0x33
+            ? this.info.new_licensee_code
+            : this.formatHex(this.info.old_licensee_code, 2);
+    }
+
+    check(valid) {
+        return valid ? html`<span>OK</span>` : html`<span class="bad">BAD</span>`;
+    }
+
+    render() {
+        if (!this.info) {
+            return html`<span>No cartridge loaded</span>`;
+        }
+        return html`
+            <div class="row">
+                <span>Title: ${this.info.title}</span>
+                <span>Code: ${this.info.manufacturer_code || '-'}</span>
+                <span>Licensee: ${this.licensee}</span>
+            </div>
+            <div class="row">
+                <span>Type: ${this.formatHex(this.info.cartridge_type, 2)}</span>
+                <span>ROM: ${this.info.rom_size / 1024} KiB</span>
+                <span>RAM: ${this.info.ram_size / 1024} KiB</span>
+                <span>Version: ${this.info.version}</span>
+            </div>
+            <div class="row">
+                <span>${this.cgb}</span>
+                <span>SGB: ${this.info.sgb ? 'YES' : 'NO'}</span>
+            </div>
+            <span>Logo: ${this.check(this.info.logo_valid)}</span>
+            <span>Header: ${this.check(this.info.header_checksum_valid)}</span>
+            <span>Global: ${this.check(this.info.global_checksum_valid)}</span>
+        `
+    }
+}
+
+customElements.define('cartridge-debug', Cartridge);
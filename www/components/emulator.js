@@ -4,12 +4,18 @@ import { Button } from 'dmg-emu';
 
 let ctx;
 
+// snapshot every 10 frames, keep up to 32MB of history
+const REWIND_INTERVAL = 10;
+const REWIND_BUDGET = 32 * 1024 * 1024;
+const REWIND_STEP = 60;
+
 class DMGScreen extends LitElement {
     static get properties() {
         return {
             play: {type: Boolean, attribute: false},
             justPaused: {type: Boolean, attribute: false},
             dmg: {attribute: false},
+            sink: {attribute: false},
             mem: {attribute: false},
             cpu: {attribute: false},
         }
@@ -81,6 +87,16 @@ class DMGScreen extends LitElement {
         if(e.key === 'Shift') {
             this.dmg.press_button(Button.Select, true);
         }
+
+        if(e.key === 'F2') {
+            this.storeState();
+        }
+        if(e.key === 'F4') {
+            this.restoreState();
+        }
+        if(e.key === 'Backspace') {
+            this.rewind();
+        }
     }
 
     handleKeyUp(e) {
@@ -122,26 +138,52 @@ class DMGScreen extends LitElement {
         event.preventDefault();
     }
 
-    emulateAudio() {
-        let buffer = this.audioCtx.createBuffer(2, 44100 / 60, 44100);
+    async startAudio() {
+        if (!this.sink) {
+            return;
+        }
+        if (!this.audioNode) {
+            await this.audioCtx.audioWorklet.addModule('audio-worklet.js');
+            this.audioNode = new AudioWorkletNode(this.audioCtx, 'dmg-audio', {
+                outputChannelCount: [2],
+                processorOptions: { buffer: this.sink.buffer() }
+            });
+            this.audioNode.connect(this.audioCtx.destination);
+        }
+        this.dmg.set_audio_rate(this.audioCtx.sampleRate);
+    }
 
-        var channelR = buffer.getChannelData(0);
-        var channelL = buffer.getChannelData(1);
-        let data = this.dmg.get_audio_channel1;
-        channelR.set(data);
-        channelL.set(data);
+    rewind() {
+        let rewound;
+        try {
+            rewound = this.dmg.rewind(REWIND_STEP);
+        } catch (e) {
+            alert(`Could not rewind: ${e.message}`);
+            return;
+        }
+        if (rewound > 0) {
+            this.draw();
+            this.dispatchEvent((new CustomEvent('frame')));
+        }
+    }
 
-        var source = this.audioCtx.createBufferSource();
-        source.buffer = buffer;
-        source.connect(this.audioCtx.destination);
-        source.start();
+    draw() {
+        let screen = new Uint8ClampedArray(this.dmg.get_buffer());
+        let data = new ImageData(screen, 160, 144);
+        this.ctx.putImageData(data, 0, 0);
     }
 
-    start() {
+    async start() {
         this.dmg.init();
+        await this.startAudio();
+        this.dmg.enable_rewind(REWIND_INTERVAL, REWIND_BUDGET);
 
         const tick = () => {
             if (!this.play) {
+                // a paused worklet would only count underruns
+                if (this.audioCtx.state === 'running') {
+                    this.audioCtx.suspend();
+                }
                 if (this.justPaused) {
                     this.dispatchEvent((new CustomEvent('update-memory')));
                 }
@@ -150,18 +192,35 @@ class DMGScreen extends LitElement {
                 return;
             }
 
+            if (this.audioCtx.state === 'suspended') {
+                this.audioCtx.resume();
+            }
+
+            this.dmg.sync_rtc(Date.now() / 1000);
             let finished_frame = this.dmg.tick_till_frame_done();
 
             
             if (!finished_frame) {
                 this.justPaused = true;
+                let fault = this.dmg.get_cpu_fault();
+                if (fault) {
+                    this.dispatchEvent(new CustomEvent('fault', {
+                        detail: { pc: fault.pc, opcode: fault.opcode }
+                    }));
+                }
                 this.dispatchEvent((new CustomEvent('break')));
+            } else {
+                this.dmg.record_frame();
             }
-            let screen = new Uint8ClampedArray(this.dmg.get_buffer());
-            let data = new ImageData(screen, 160, 144);
-            this.ctx.putImageData(data, 0, 0);
+            this.draw();
 
-            //this.emulateAudio();
+            if (this.sink) {
+                this.sink.feed(this.dmg);
+            }
+
+            if (this.dmg.has_battery() && this.dmg.is_save_dirty()) {
+                this.storeSave();
+            }
 
             this.dispatchEvent((new CustomEvent('frame')));
 
@@ -171,12 +230,64 @@ class DMGScreen extends LitElement {
         requestAnimationFrame(tick);
     }
 
+    storeSave() {
+        let save = this.dmg.export_save(Date.now() / 1000);
+        let binary = '';
+        save.forEach(byte => binary += String.fromCharCode(byte));
+        let encoded = btoa(binary);
+        localStorage.setItem(`save:${this.romName}`, encoded);
+    }
+
+    storeState() {
+        let state = this.dmg.save_state();
+        let binary = '';
+        state.forEach(byte => binary += String.fromCharCode(byte));
+        localStorage.setItem(`state:${this.romName}`, btoa(binary));
+    }
+
+    restoreState() {
+        let encoded = localStorage.getItem(`state:${this.romName}`);
+        if (!encoded) {
+            return;
+        }
+        let state = Uint8Array.from(atob(encoded), c => c.charCodeAt(0));
+        try {
+            this.dmg.load_state(state);
+        } catch (e) {
+            alert(`Could not load state: ${e.message}`);
+            return;
+        }
+        this.dispatchEvent((new CustomEvent('frame')));
+    }
+
+    restoreSave() {
+        let encoded = localStorage.getItem(`save:${this.romName}`);
+        if (!encoded || !this.dmg.has_battery()) {
+            return;
+        }
+        let save = Uint8Array.from(atob(encoded), c => c.charCodeAt(0));
+        this.dmg.import_save(save);
+    }
+
     async handleDrop (event) {
         event.preventDefault();
         let file = event.dataTransfer.items[0].getAsFile();
         let data = await file.arrayBuffer();
         let rom = new Uint8Array(data);
-        this.dmg.load_rom_data(rom);
+        try {
+            if (file.name.toLowerCase().endsWith('.gbs')) {
+                this.dmg.load_gbs(rom);
+            } else {
+                this.dmg.load_rom_data(rom);
+            }
+        } catch (e) {
+            alert(`Could not load ${file.name}: ${e.message}`);
+            return;
+        }
+        this.romName = file.name;
+        // MBC3 clocks follow the wall clock, across sessions through the save trailer
+        this.dmg.use_host_clock(true);
+        this.restoreSave();
         this.ctx = this.shadowRoot.querySelector('#screen').getContext('2d');
         this.start();
     }
@@ -0,0 +1,59 @@
+import { LitElement, html, css } from "lit-element";
+
+class GbsPlayer extends LitElement {
+    static get properties() {
+        return {
+            dmg: {attribute: false},
+            info: {attribute: false},
+            track: {attribute: false}
+        }
+    }
+
+    static get styles() {
+        return css`
+            :host {
+                width: calc(100% - 10px);
+                padding: 10px;
+            }
+            .row {
+                margin-bottom: 10px;
+            }
+            button {
+                border: none;
+                background-color: transparent;
+                font-size: 20px;
+                cursor: pointer;
+            }
+        `;
+    }
+
+    select(track) {
+        if (track < 0 || track >= this.info.track_count) {
+            return;
+        }
+        this.dmg.select_track(track);
+        this.track = track;
+    }
+
+    render() {
+        if (!this.info) {
+            return html`<span>No GBS file loaded</span>`;
+        }
+        return html`
+            <div class="row">
+                <span>Title: ${this.info.title}</span>
+            </div>
+            <div class="row">
+                <span>Author: ${this.info.author}</span>
+                <span>Copyright: ${this.info.copyright}</span>
+            </div>
+            <div class="row">
+                <button @click=${() => this.select(this.track - 1)}>⏮</button>
+                <span>Track ${this.track + 1} / ${this.info.track_count}</span>
+                <button @click=${() => this.select(this.track + 1)}>⏭</button>
+            </div>
+        `
+    }
+}
+
+customElements.define('gbs-player', GbsPlayer);
@@ -6,6 +6,9 @@ import "./components/oam"
 import "./components/serial"
 import "./components/joypad"
 import "./components/timer"
+import "./components/cartridge"
+import "./components/audio"
+import "./components/gbs"
 import "./components/shared/card"
 import "./components/shared/tabbed"
 import "./components/emulator"
\ No newline at end of file